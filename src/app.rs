@@ -1,8 +1,109 @@
-use std::{error, fs, path::PathBuf, str::FromStr};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    error, fs,
+    io::{Read, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use ratatui::layout::Rect;
+
+use crate::{
+    cli::Args,
+    csv,
+    editor::LineEditor,
+    file,
+    filter::{Conditions, Filter},
+    goto::{self, Target},
+    history::{History, Operation},
+    prompt::{Prompt, PromptKind},
+    reorder::ColumnOrder,
+    search::{Replace, Scope, Search},
+    sort::SortKeys,
+    stream::Stream,
+    types::{ColumnType, TypeGuess},
+};
+
+/// Files larger than this many bytes are streamed instead of loaded.
+const STREAM_THRESHOLD: u64 = 64 << 20;
+
+/// Bytes read from the start of a streamed file to detect its format.
+const STREAM_HEAD: usize = 64 << 10;
 
 /// Application result type.
 pub type AppResult<T> = std::result::Result<T, Box<dyn error::Error>>;
 
+/// Message shown in the path bar until the next key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    /// Something worth knowing.
+    Info(String),
+    /// Something went wrong.
+    Error(String),
+}
+
+/// Longest gap between two clicks on a cell that still counts as a double-click.
+const DOUBLE_CLICK: Duration = Duration::from_millis(500);
+
+/// Rows moved by one notch of the scroll wheel.
+const WHEEL_ROWS: usize = 3;
+
+/// Where the table was drawn by the last render, for finding what a click hit.
+#[derive(Debug, Default, Clone)]
+pub struct TableLayout {
+    /// Inside of the table's border.
+    pub area: Rect,
+    /// Is the top line the header row?
+    pub header: bool,
+    /// Row drawn on each line below the header.
+    pub rows: Vec<usize>,
+    /// Each column drawn, with the screen column it starts at and its width.
+    pub columns: Vec<(usize, u16, u16)>,
+}
+
+/// Part of the table under the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Hit {
+    /// The header cell of a column.
+    Header(usize),
+    /// The cell at a row and column.
+    Cell(usize, usize),
+    /// The gap just right of a column, dragged to resize it.
+    Border(usize),
+}
+
+impl TableLayout {
+    /// What is drawn at screen position `x`, `y`, if anything.
+    pub fn hit(&self, x: u16, y: u16) -> Option<Hit> {
+        let area = self.area;
+        if x < area.x || x >= area.right() || y < area.y || y >= area.bottom() {
+            return None;
+        }
+        if let Some((col, ..)) = self
+            .columns
+            .iter()
+            .find(|(_, start, width)| x == start + width)
+        {
+            return Some(Hit::Border(*col));
+        }
+        let (col, ..) = self
+            .columns
+            .iter()
+            .find(|(_, start, width)| (*start..start + width).contains(&x))?;
+        let line = (y - area.y) as usize;
+        match (self.header, line) {
+            (true, 0) => Some(Hit::Header(*col)),
+            (header, line) => {
+                let row = self.rows.get(line - header as usize)?;
+                Some(Hit::Cell(*row, *col))
+            }
+        }
+    }
+}
+
 /// Application.
 #[derive(Debug)]
 pub struct App {
@@ -10,14 +111,24 @@ pub struct App {
     pub running: bool,
     /// values in the csv file
     pub value_matrix: Vec<Vec<String>>,
-    /// cursors pos
-    pub cursor_pos: usize,
-    /// current string being looked at
-    pub current_value: String,
+    /// current string being looked at, and the cursor in it
+    pub editor: LineEditor,
     /// current cell being looked at
     pub current_location: (usize, usize),
+    /// first column scrolled into view
+    pub col_offset: usize,
+    /// first row scrolled into view below the header
+    pub row_offset: usize,
+    /// rows that fit in the table, as of the last render
+    pub page_rows: usize,
+    /// leading columns kept in view while scrolling sideways
+    pub frozen_cols: usize,
     /// is the user editing?
     pub editing: bool,
+    /// editing in the popup, where Enter starts a new line
+    pub multiline: bool,
+    /// value refused for not fitting its column, kept if entered again
+    pub rejected: Option<String>,
     /// path to file
     pub path: String,
     /// first row as headers
@@ -28,8 +139,54 @@ pub struct App {
     pub controls: bool,
     /// Is graphing
     pub is_graph: bool,
-    /// Previous actions
-    pub previous_matrices: Vec<String>,
+    /// undo and redo stacks
+    pub history: History,
+    /// keep the undo history next to the file between sessions
+    pub persist_history: bool,
+    /// has the matrix changed since the last save?
+    pub dirty: bool,
+    /// asking whether to quit with unsaved changes
+    pub confirming_quit: bool,
+    /// message for the path bar
+    pub status: Option<Status>,
+    /// keep a copy of the file as it was before the first save
+    pub backup: bool,
+    /// has the backup been written?
+    pub backed_up: bool,
+    /// which cells were quoted in the file
+    pub quoted_cells: Vec<Vec<bool>>,
+    /// layout used when writing the file
+    pub format: csv::Format,
+    /// rows read on demand when the file is too large to load
+    pub stream: Option<Stream>,
+    /// viewing only, editing keys are ignored
+    pub read_only: bool,
+    /// where the table was drawn, as of the last render
+    pub table_layout: TableLayout,
+    /// column widths set by dragging a border
+    pub col_widths: HashMap<usize, u16>,
+    /// column whose border is being dragged, with the mouse column and width it started at
+    pub resizing: Option<(usize, u16, u16)>,
+    /// time and cell of the last click, to spot double-clicks
+    pub last_click: Option<(Instant, (usize, usize))>,
+    /// column last sorted by clicking its header, and whether descending
+    pub sorted_by: Option<(usize, bool)>,
+    /// line being typed into the path bar
+    pub prompt: Option<Prompt>,
+    /// last search and the cells it found
+    pub search: Search,
+    /// find and replace waiting for each match to be confirmed
+    pub replacing: Option<Replace>,
+    /// corner of the selection opposite the cursor, while selecting
+    pub anchor: Option<(usize, usize)>,
+    /// cells last copied or cut, row by row
+    pub register: Vec<Vec<String>>,
+    /// columns being put in a new order in the reorder dialog
+    pub reordering: Option<ColumnOrder>,
+    /// rows left showing while the others are hidden
+    pub filter: Option<Filter>,
+    /// type guessed for each column from the rows below the header
+    pub col_types: Vec<TypeGuess>,
 }
 
 impl Default for App {
@@ -37,212 +194,1629 @@ impl Default for App {
         Self {
             running: true,
             value_matrix: Vec::new(),
-            cursor_pos: 0,
-            current_value: String::new(),
+            editor: LineEditor::default(),
             current_location: (0, 0),
+            col_offset: 0,
+            row_offset: 0,
+            page_rows: 1,
+            frozen_cols: 0,
             editing: false,
+            multiline: false,
+            rejected: None,
             path: String::new(),
             has_header_row: false,
             has_label_col: false,
             is_graph: false,
-            previous_matrices: Vec::new(),
+            history: History::default(),
+            persist_history: false,
+            dirty: false,
+            confirming_quit: false,
+            status: None,
+            backup: false,
+            backed_up: false,
             controls: true,
+            quoted_cells: Vec::new(),
+            format: csv::Format::default(),
+            stream: None,
+            read_only: false,
+            table_layout: TableLayout::default(),
+            col_widths: HashMap::new(),
+            resizing: None,
+            last_click: None,
+            sorted_by: None,
+            prompt: None,
+            search: Search::default(),
+            replacing: None,
+            anchor: None,
+            register: Vec::new(),
+            reordering: None,
+            filter: None,
+            col_types: Vec::new(),
         }
     }
 }
 
 impl App {
     /// Constructs a new instance of [`App`].
-    pub fn new(path: String) -> Self {
-        let absolute_path = PathBuf::from_str(path.as_str()).unwrap().canonicalize().unwrap();
-        let file = std::fs::read_to_string(absolute_path.clone()).expect("File read error");
-        let value_matrix: Vec<Vec<String>> = file
-            .split('\n')
-            .map(|x| x.to_string())
-            .map(|x: String| x.split(',').map(|x| x.to_string()).collect::<Vec<String>>())
-            .collect();
-
-        Self {
-            running: true,
-            current_value: value_matrix.clone()[0][0].clone(),
-            value_matrix: value_matrix.clone(),
-            cursor_pos: value_matrix.clone()[0][0].clone().len(),
-            current_location: (0, 0),
-            editing: false,
+    pub fn new(args: &Args) -> Self {
+        let absolute_path = PathBuf::from_str(args.path.as_str())
+            .unwrap()
+            .canonicalize()
+            .unwrap();
+        let len = fs::metadata(&absolute_path).expect("File read error").len();
+        let mut app = Self {
             path: absolute_path.to_string_lossy().to_string(),
-            has_header_row: false,
-            has_label_col: false,
-            is_graph: false,
-            previous_matrices: vec![],
-            controls: true,
+            backup: args.backup,
+            read_only: args.read_only,
+            frozen_cols: args.freeze_cols,
+            ..Self::default()
+        };
+        if args.read_only || args.stream || len > STREAM_THRESHOLD {
+            app.open_stream(args);
+        } else {
+            app.open_in_memory(args);
+        }
+        app.format.quote_style = args.quote_style;
+        app.update_curr();
+        app
+    }
+    /// Reads the whole file into the value matrix.
+    fn open_in_memory(&mut self, args: &Args) {
+        let file = fs::read_to_string(&self.path).expect("File read error");
+        let document = load(&file, args.delimiter);
+        self.value_matrix = document.records;
+        self.quoted_cells = document.quoted;
+        self.format = document.format;
+        self.infer_types();
+        self.persist_history = args.history;
+        if args.history {
+            match History::restore(Path::new(&self.path), file.as_bytes()) {
+                Ok(history) => self.history = history.unwrap_or_default(),
+                Err(err) => {
+                    self.status = Some(Status::Error(format!("Undo history discarded: {err}")))
+                }
+            }
+        }
+    }
+    /// Indexes the file in the background and reads rows as they are shown.
+    ///
+    /// Read-only files are memory-mapped rather than read. The delimiter,
+    /// line ending and initial width come from the head of the file.
+    ///
+    /// The undo history is never persisted for streamed files, as that
+    /// would mean hashing all of it on every save.
+    fn open_stream(&mut self, args: &Args) {
+        let mut head = Vec::new();
+        fs::File::open(&self.path)
+            .and_then(|file| file.take(STREAM_HEAD as u64).read_to_end(&mut head))
+            .expect("File read error");
+        let head = String::from_utf8_lossy(&head);
+        let document = load(&head, args.delimiter);
+        self.format = document.format;
+        let open = match self.read_only {
+            true => Stream::map,
+            false => Stream::open,
+        };
+        self.stream = Some(
+            open(
+                Path::new(&self.path),
+                self.format.delimiter,
+                document.records[0].len(),
+            )
+            .expect("File read error"),
+        );
+        if args.history {
+            self.status = Some(Status::Info(
+                "Undo history is not kept for streamed files".to_string(),
+            ));
+        }
+    }
+
+    /// Number of rows, only counting those indexed so far when streaming.
+    pub fn row_count(&self) -> usize {
+        match &self.stream {
+            Some(stream) => stream.rows().max(1),
+            None => self.value_matrix.len(),
+        }
+    }
+    /// Number of columns.
+    pub fn col_count(&self) -> usize {
+        match &self.stream {
+            Some(stream) => stream.cols(),
+            None => self.value_matrix[0].len(),
+        }
+    }
+    /// Cells of the row at `row`.
+    pub fn row(&mut self, row: usize) -> Vec<String> {
+        match &mut self.stream {
+            Some(stream) => stream.row(row),
+            None => self.value_matrix[row].clone(),
+        }
+    }
+    /// Number of rows shown, all of them unless a filter hides some.
+    pub fn shown_rows(&self) -> usize {
+        match &self.filter {
+            Some(filter) => filter.rows.len(),
+            None => self.row_count(),
+        }
+    }
+    /// Row shown at place `i`, counting from the top.
+    pub fn view_row(&self, i: usize) -> usize {
+        match &self.filter {
+            Some(filter) => filter.rows[i],
+            None => i,
+        }
+    }
+    /// Place among the shown rows of `row`, or of the first shown row after
+    /// it, kept inside the view.
+    pub fn view_index(&self, row: usize) -> usize {
+        match &self.filter {
+            Some(filter) => filter.index(row).min(filter.rows.len() - 1),
+            None => row,
+        }
+    }
+    pub fn is_shown(&self, row: usize) -> bool {
+        self.filter.as_ref().is_none_or(|filter| filter.shows(row))
+    }
+    /// Guesses the type of every column. Streamed files are left untyped,
+    /// as their rows aren't all at hand.
+    fn infer_types(&mut self) {
+        self.col_types.clear();
+        if self.stream.is_none() {
+            let cols = self.value_matrix[0].len();
+            self.col_types.resize(cols, TypeGuess::default());
+            for col in 0..cols {
+                self.infer_col(col);
+            }
         }
     }
+    fn infer_col(&mut self, col: usize) {
+        let header_rows = (self.has_header_row as usize).min(self.value_matrix.len());
+        if let Some(guess) = self.col_types.get_mut(col) {
+            *guess = ColumnType::infer(
+                self.value_matrix[header_rows..]
+                    .iter()
+                    .map(|row| row[col].as_str()),
+            );
+        }
+    }
+    /// Type guessed for column `col`, with how sure the guess is, or
+    /// `None` when the columns aren't typed, as in streamed files.
+    pub fn col_guess(&self, col: usize) -> Option<TypeGuess> {
+        self.col_types.get(col).copied()
+    }
+    /// Type of column `col`, text when the columns aren't typed.
+    pub fn col_type(&self, col: usize) -> ColumnType {
+        self.col_guess(col)
+            .map_or(ColumnType::Text, |guess| guess.kind)
+    }
+    /// Value of the cell at `row`, `col`.
+    pub fn cell(&mut self, row: usize, col: usize) -> String {
+        match &mut self.stream {
+            Some(stream) => stream.row(row).swap_remove(col),
+            None => self.value_matrix[row][col].clone(),
+        }
+    }
+    /// Whether the whole file is in memory and may change, telling the user otherwise.
+    ///
+    /// Changes to the shape of the table, and anything else that needs
+    /// every row at once, only work on files that are not streamed.
+    fn in_memory(&mut self) -> bool {
+        if self.read_only {
+            self.refuse_read_only();
+            return false;
+        }
+        self.loaded()
+    }
+    /// Whether every row can be looked at, telling the user otherwise.
+    fn loaded(&mut self) -> bool {
+        if self.stream.is_some() {
+            self.status = Some(Status::Info(
+                "Not available while streaming a large file".to_string(),
+            ));
+        }
+        self.stream.is_none()
+    }
+    /// Tells the user that a key which would change the file was ignored.
+    pub fn refuse_read_only(&mut self) {
+        self.status = Some(Status::Info("The file is open read-only".to_string()));
+    }
 
     /// Handles the tick event of the terminal.
-    pub fn tick(&self) {}
+    ///
+    /// While a streamed file is still being indexed the cell under the
+    /// cursor may only now have become readable.
+    pub fn tick(&mut self) {
+        if !self.editing && self.stream.as_ref().is_some_and(|s| s.progress().is_some()) {
+            self.update_curr();
+        }
+    }
 
     /// Set running to false to quit the application.
     pub fn quit(&mut self) {
         self.running = false;
     }
+    /// Asks to quit, first confirming if there are unsaved changes,
+    /// counting an edit still open because its value was refused.
+    pub fn request_quit(&mut self) {
+        if self.dirty || self.editing {
+            self.confirming_quit = true;
+        } else {
+            self.quit();
+        }
+    }
+    /// Returns to the grid from the quit confirmation.
+    pub fn cancel_quit(&mut self) {
+        self.confirming_quit = false;
+    }
+    /// Loads the cell under the cursor into the edit buffer.
     pub fn update_curr(&mut self) {
-        let file = std::fs::read_to_string(self.path.clone()).expect("File read error");
-        let value_matrix: Vec<Vec<String>> = file
-            .split('\n')
-            .map(|x| x.to_string())
-            .map(|x: String| x.split(',').map(|x| x.to_string()).collect::<Vec<String>>())
-            .collect();
-        self.value_matrix = value_matrix;
-
-        self.current_value =
-            self.value_matrix[self.current_location.1][self.current_location.0].clone();
-        self.cursor_pos = self.current_value.len();
+        self.editor = LineEditor::new(self.cell(self.current_location.1, self.current_location.0));
     }
 
     pub fn move_up(&mut self) {
-        match self.current_location.1.checked_sub(1) {
-            Some(j) => self.current_location.1 = j,
-            None => {}
-        };
+        let i = self.view_index(self.current_location.1);
+        if let Some(j) = i.checked_sub(1) {
+            self.current_location.1 = self.view_row(j);
+        }
         self.update_curr();
     }
     pub fn move_down(&mut self) {
-        match self.current_location.1 > self.value_matrix.len() - 2 {
-            false => self.current_location.1 = self.current_location.1 + 1,
-            true => {}
-        };
+        let i = self.view_index(self.current_location.1);
+        if i + 1 < self.shown_rows() {
+            self.current_location.1 = self.view_row(i + 1);
+        }
         self.update_curr();
     }
     pub fn move_right(&mut self) {
-        match self.current_location.0 > self.value_matrix[0].len() - 2 {
-            false => self.current_location.0 = self.current_location.0 + 1,
-            true => {}
-        };
+        if self.current_location.0 + 1 < self.col_count() {
+            self.current_location.0 += 1;
+        }
         self.update_curr();
     }
     pub fn move_left(&mut self) {
-        match self.current_location.0.checked_sub(1) {
-            Some(j) => self.current_location.0 = j,
+        if let Some(j) = self.current_location.0.checked_sub(1) {
+            self.current_location.0 = j;
+        }
+        self.update_curr();
+    }
+    /// Moves the cursor to `row`, `col`, kept inside the table. A row
+    /// hidden by the filter gives way to the next one shown.
+    pub fn move_to(&mut self, row: usize, col: usize) {
+        let row = self.view_row(self.view_index(row.min(self.row_count() - 1)));
+        self.current_location = (col.min(self.col_count() - 1), row);
+        self.update_curr();
+    }
+    pub fn page_up(&mut self) {
+        let (col, row) = self.current_location;
+        let i = self.view_index(row).saturating_sub(self.page_rows);
+        self.move_to(self.view_row(i), col);
+    }
+    pub fn page_down(&mut self) {
+        let (col, row) = self.current_location;
+        let i = (self.view_index(row) + self.page_rows).min(self.shown_rows() - 1);
+        self.move_to(self.view_row(i), col);
+    }
+    pub fn move_row_start(&mut self) {
+        self.move_to(self.current_location.1, 0);
+    }
+    pub fn move_row_end(&mut self) {
+        self.move_to(self.current_location.1, usize::MAX);
+    }
+    pub fn move_first_cell(&mut self) {
+        self.move_to(0, 0);
+    }
+    pub fn move_last_cell(&mut self) {
+        self.move_to(usize::MAX, usize::MAX);
+    }
+    /// Handles a left click at screen position `x`, `y`.
+    ///
+    /// A click on a cell selects it and a second one soon after edits it,
+    /// a click on a header sorts by that column, and a click just right of
+    /// a column starts resizing it.
+    pub fn click(&mut self, x: u16, y: u16) {
+        match self.table_layout.hit(x, y) {
+            Some(Hit::Border(col)) => {
+                let width = self
+                    .table_layout
+                    .columns
+                    .iter()
+                    .find(|(j, ..)| *j == col)
+                    .map_or(0, |(.., width)| *width);
+                self.resizing = Some((col, x, width));
+            }
+            Some(Hit::Header(col)) => self.sort_by_col(col),
+            Some(Hit::Cell(row, col)) => {
+                let double = self.last_click.is_some_and(|(time, cell)| {
+                    cell == (row, col) && time.elapsed() <= DOUBLE_CLICK
+                });
+                self.move_to(row, col);
+                if !double {
+                    self.last_click = Some((Instant::now(), (row, col)));
+                } else if self.read_only {
+                    self.refuse_read_only();
+                } else {
+                    self.last_click = None;
+                    self.enter_editing();
+                }
+            }
             None => {}
+        }
+    }
+    /// Resizes the column whose border is being dragged to follow the mouse to column `x`.
+    pub fn drag(&mut self, x: u16) {
+        if let Some((col, start, width)) = self.resizing {
+            let width = (width as i32 + x as i32 - start as i32).max(1) as u16;
+            self.col_widths.insert(col, width);
+        }
+    }
+    pub fn release(&mut self) {
+        self.resizing = None;
+    }
+    /// Scrolls a notch of the wheel up or down, taking the cursor along when it would leave the view.
+    pub fn scroll_rows(&mut self, down: bool) {
+        let header_rows = self.has_header_row as usize;
+        let offset = self.row_offset.max(header_rows);
+        self.row_offset = match down {
+            true => (offset + WHEEL_ROWS)
+                .min(self.shown_rows().saturating_sub(self.page_rows))
+                .max(header_rows),
+            false => offset.saturating_sub(WHEEL_ROWS).max(header_rows),
         };
-        self.update_curr();
+        let (col, row) = self.current_location;
+        let i = self.view_index(row);
+        if i >= header_rows {
+            let i = i
+                .clamp(self.row_offset, self.row_offset + self.page_rows - 1)
+                .min(self.shown_rows() - 1);
+            self.move_to(self.view_row(i), col);
+        }
+    }
+    /// Scrolls one column left or right, taking the cursor along when it would leave the view.
+    pub fn scroll_cols(&mut self, right: bool) {
+        let frozen = self.frozen_cols();
+        let shown = self
+            .table_layout
+            .columns
+            .len()
+            .saturating_sub(frozen)
+            .max(1);
+        let offset = self.col_offset.max(frozen);
+        self.col_offset = match right {
+            true => (offset + 1).min(self.col_count() - 1),
+            false => offset.saturating_sub(1),
+        }
+        .max(frozen);
+        let (col, row) = self.current_location;
+        if col >= frozen {
+            self.move_to(row, col.clamp(self.col_offset, self.col_offset + shown - 1));
+        }
+    }
+    /// Inserts `text` at the edit cursor, with line breaks as `\n`.
+    pub fn insert_text(&mut self, text: &str) {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        self.editor.insert_str(&text);
     }
-    pub fn edit(&mut self, ch: char) {
-        self.current_value.insert(self.cursor_pos, ch);
-        self.cursor_pos += 1;
+    /// Fills the cells from the cursor with tab-separated rows of `text`,
+    /// as copied from a spreadsheet, growing the table to fit.
+    pub fn paste(&mut self, text: &str) {
+        if self.in_memory() {
+            self.paste_block(&csv::parse(text, '\t').records);
+        }
     }
-    pub fn backspace(&mut self) {
-        match self.cursor_pos {
-            0 => {}
-            _ => {
-                self.current_value.remove(self.cursor_pos - 1);
-                self.cursor_pos -= 1;
+    /// Fills the cells from the cursor with the block last copied or cut.
+    pub fn paste_register(&mut self) {
+        self.anchor = None;
+        if !self.in_memory() {
+            return;
+        }
+        match self.register.is_empty() {
+            true => self.status = Some(Status::Info("Nothing has been copied".to_string())),
+            false => self.paste_block(&self.register.clone()),
+        }
+    }
+    /// Fills the cells from the cursor with `records`, growing the table to
+    /// fit. Rows hidden by the filter are passed over.
+    fn paste_block(&mut self, records: &[Vec<String>]) {
+        let (col, row) = self.current_location;
+        let width = records.iter().map(|record| record.len()).max().unwrap_or(0);
+        if width == 0 {
+            return;
+        }
+        self.begin_group();
+        while self.value_matrix[0].len() < col + width {
+            self.perform(Operation::InsertCol {
+                index: self.value_matrix[0].len(),
+                cells: vec![String::new(); self.value_matrix.len()],
+            });
+        }
+        let mut targets: Vec<usize> = (self.view_index(row)..self.shown_rows())
+            .map(|i| self.view_row(i))
+            .take(records.len())
+            .collect();
+        while targets.len() < records.len() {
+            targets.push(self.value_matrix.len());
+            self.perform(Operation::InsertRow {
+                index: self.value_matrix.len(),
+                cells: vec![String::new(); self.value_matrix[0].len()],
+            });
+        }
+        for (record, target) in records.iter().zip(targets) {
+            for (j, new) in record.iter().enumerate() {
+                let old = &self.value_matrix[target][col + j];
+                if old != new {
+                    self.perform(Operation::EditCell {
+                        row: target,
+                        col: col + j,
+                        old: old.clone(),
+                        new: new.clone(),
+                    });
+                }
             }
         }
+        self.end_group();
+        self.move_to(row, col);
+        self.status = Some(Status::Info(format!(
+            "Pasted {} by {} cells",
+            records.len(),
+            width
+        )));
     }
     pub fn enter_editing(&mut self) {
-        self.save(true);
         if !self.is_graph {
+            self.update_curr();
             self.editing = true;
         }
     }
+    /// Edits the cell in a popup large enough for long and multi-line values.
+    pub fn enter_popup_editing(&mut self) {
+        self.enter_editing();
+        self.multiline = self.editing;
+    }
+    /// Leaves editing and stores the value, unless it doesn't fit the type
+    /// of its column. Such a value is refused, leaving the cell open, and
+    /// only kept when it is entered a second time.
     pub fn exit_editing(&mut self) {
-        self.save(false);
-        self.update_curr();
+        let (x, y) = self.current_location;
+        let old = self.cell(y, x);
+        if old != self.editor.value {
+            let kind = self.col_type(x);
+            let new = self.editor.value.clone();
+            let header = self.has_header_row && y == 0;
+            let fits = header || new.trim().is_empty() || kind.fits(&new);
+            if !fits && self.rejected.as_ref() != Some(&new) {
+                self.status = Some(Status::Error(format!(
+                    "Column {} holds {}, not `{}`: confirm again to keep it, Esc to discard",
+                    goto::col_letters(x),
+                    kind.describe(),
+                    new.trim().replace(['\r', '\n'], " ")
+                )));
+                self.rejected = Some(new);
+                return;
+            }
+            self.perform(Operation::EditCell {
+                row: y,
+                col: x,
+                old,
+                new,
+            });
+        }
         self.editing = false;
+        self.multiline = false;
+        self.rejected = None;
+    }
+    /// Leaves editing without changing the cell.
+    pub fn cancel_editing(&mut self) {
+        self.editing = false;
+        self.multiline = false;
+        self.rejected = None;
+        self.update_curr();
     }
     pub fn toggle_header_row(&mut self) {
         self.has_header_row = !self.has_header_row;
+        self.infer_types();
+        if let Some(filter) = self.filter.take() {
+            self.apply_filter(&filter.text);
+        }
     }
     pub fn toggle_label_col(&mut self) {
         self.has_label_col = !self.has_label_col;
     }
-    pub fn save(&mut self, undoable: bool) {
-        if undoable == true {
-            self.previous_matrices
-                .push(fs::read_to_string(self.path.clone()).unwrap());
-        }
-        self.value_matrix[self.current_location.1][self.current_location.0] =
-            self.current_value.clone();
-        let _ = std::fs::write(
-            self.path.clone(),
-            self.value_matrix
-                .clone()
-                .into_iter()
-                .enumerate()
-                .fold(String::new(), |acc, (j, x)| {
-                    let y = x
-                        .into_iter()
-                        .enumerate()
-                        .fold(String::new(), |acc, (i, x)| {
-                            if acc.is_empty() {
-                                if (i, j) == self.current_location && !undoable {
-                                    if self.current_value.is_empty() {
-                                        format!(" ")
-                                    } else {
-                                        format!("{}", self.current_value)
-                                    }
-                                } else {
-                                    format!("{}", x)
-                                }
-                            } else {
-                                if (i, j) == self.current_location {
-                                    if self.current_value.is_empty() {
-                                        format!("{},", acc)
-                                    } else {
-                                        format!("{},{}", acc, self.current_value)
-                                    }
-                                } else {
-                                    format!("{},{}", acc, x)
-                                }
-                            }
-                        });
-                    if acc.is_empty() {
-                        format!("{y}")
-                    } else {
-                        format!("{acc}\n{y}")
+    /// Number of leading columns pinned on screen, at least the label column when shown.
+    pub fn frozen_cols(&self) -> usize {
+        self.frozen_cols
+            .max(self.has_label_col as usize)
+            .min(self.col_count())
+    }
+    pub fn freeze_more_cols(&mut self) {
+        self.frozen_cols = (self.frozen_cols() + 1).min(self.col_count());
+    }
+    pub fn freeze_fewer_cols(&mut self) {
+        self.frozen_cols = self.frozen_cols().saturating_sub(1);
+    }
+    /// Applies `operation` and records it so it can be undone.
+    pub fn perform(&mut self, operation: Operation) {
+        self.apply(&operation);
+        self.history.record(operation);
+    }
+    /// Changes the matrix as described by `operation`, then brings
+    /// everything derived from it up to date.
+    fn apply(&mut self, operation: &Operation) {
+        self.change(operation);
+        self.dirty = true;
+        // A group is brought up to date once, when it ends.
+        if !self.history.is_grouping() {
+            self.refresh(Some(operation));
+        }
+        self.clamp_cursor();
+    }
+    /// Brings the column types and search matches up to date after
+    /// `operation`, or after any change at all.
+    fn refresh(&mut self, operation: Option<&Operation>) {
+        match operation {
+            Some(Operation::EditCell { col, .. }) => self.infer_col(*col),
+            // Moving rows leaves every column holding the same cells.
+            Some(Operation::ReorderRows { .. }) => {}
+            Some(Operation::ReorderCols { order }) => {
+                self.col_types = order
+                    .iter()
+                    .filter_map(|col| self.col_guess(*col))
+                    .collect();
+            }
+            _ => self.infer_types(),
+        }
+        if self.search.is_active() {
+            self.run_search();
+        }
+    }
+    /// Starts collecting operations into one undo step.
+    fn begin_group(&mut self) {
+        self.history.begin_group();
+    }
+    /// Records the operations since [`App::begin_group`] as one undo step
+    /// and brings everything derived from them up to date.
+    fn end_group(&mut self) {
+        self.history.end_group();
+        self.refresh(None);
+    }
+    /// Changes the matrix as described by `operation`.
+    fn change(&mut self, operation: &Operation) {
+        match operation {
+            Operation::EditCell { row, col, new, .. } => {
+                match &mut self.stream {
+                    Some(stream) => stream.set(*row, *col, new.clone()),
+                    None => self.value_matrix[*row][*col] = new.clone(),
+                }
+                self.current_location = (*col, *row);
+            }
+            Operation::InsertRow { index, cells } => {
+                self.value_matrix.insert(*index, cells.clone());
+                self.quoted_cells.insert(*index, vec![false; cells.len()]);
+                if let Some(filter) = &mut self.filter {
+                    filter.inserted(*index);
+                }
+            }
+            Operation::DeleteRow { index, .. } => {
+                self.value_matrix.remove(*index);
+                self.quoted_cells.remove(*index);
+                if let Some(filter) = &mut self.filter {
+                    filter.deleted(*index);
+                }
+                // Deleting the last row shown leaves nothing to filter.
+                if matches!(self.filtered_rows(), Some((0, _))) {
+                    self.filter = None;
+                    self.status = Some(Status::Info(
+                        "No rows left to filter, showing all rows".to_string(),
+                    ));
+                }
+            }
+            Operation::InsertCol { index, cells } => {
+                for (row, cell) in self.value_matrix.iter_mut().zip(cells) {
+                    row.insert(*index, cell.clone());
+                }
+                for row in &mut self.quoted_cells {
+                    row.insert(*index, false);
+                }
+                self.col_widths = self
+                    .col_widths
+                    .drain()
+                    .map(|(col, width)| (col + (col >= *index) as usize, width))
+                    .collect();
+            }
+            Operation::DeleteCol { index, .. } => {
+                for row in &mut self.value_matrix {
+                    row.remove(*index);
+                }
+                for row in &mut self.quoted_cells {
+                    row.remove(*index);
+                }
+                self.col_widths = self
+                    .col_widths
+                    .drain()
+                    .filter(|(col, _)| col != index)
+                    .map(|(col, width)| (col - (col > *index) as usize, width))
+                    .collect();
+            }
+            Operation::ReorderRows { order } => {
+                self.value_matrix = order
+                    .iter()
+                    .map(|i| self.value_matrix[*i].clone())
+                    .collect();
+                self.quoted_cells = order
+                    .iter()
+                    .map(|i| self.quoted_cells[*i].clone())
+                    .collect();
+                if let Some(filter) = &mut self.filter {
+                    filter.reordered(order);
+                }
+            }
+            Operation::ReorderCols { order } => {
+                for row in &mut self.value_matrix {
+                    *row = order.iter().map(|j| row[*j].clone()).collect();
+                }
+                for row in &mut self.quoted_cells {
+                    *row = order.iter().map(|j| row[*j]).collect();
+                }
+                self.col_widths = order
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(i, j)| Some((i, *self.col_widths.get(j)?)))
+                    .collect();
+            }
+            Operation::Group(operations) => {
+                for operation in operations {
+                    self.change(operation);
+                }
+            }
+        }
+    }
+    /// Keeps the cursor inside the matrix, and on a shown row, after its shape changed.
+    fn clamp_cursor(&mut self) {
+        self.current_location.1 = self.current_location.1.min(self.row_count() - 1);
+        self.current_location.1 = self.view_row(self.view_index(self.current_location.1));
+        self.current_location.0 = self.current_location.0.min(self.col_count() - 1);
+        let (rows, cols) = (self.row_count(), self.col_count());
+        if let Some((col, row)) = &mut self.anchor {
+            *row = (*row).min(rows - 1);
+            *col = (*col).min(cols - 1);
+        }
+        self.update_curr();
+    }
+    /// Rows and columns of the rectangle between the selection anchor and the cursor.
+    pub fn selection(&self) -> Option<(Range<usize>, Range<usize>)> {
+        let (anchor_col, anchor_row) = self.anchor?;
+        let (col, row) = self.current_location;
+        Some((
+            anchor_row.min(row)..anchor_row.max(row) + 1,
+            anchor_col.min(col)..anchor_col.max(col) + 1,
+        ))
+    }
+    /// Anchors a selection at the cursor unless one is already being made.
+    pub fn start_selection(&mut self) {
+        self.anchor.get_or_insert(self.current_location);
+    }
+    pub fn toggle_selection(&mut self) {
+        self.anchor = match self.anchor {
+            Some(_) => None,
+            None => Some(self.current_location),
+        };
+    }
+    /// Copies the selected cells into the register and leaves selection mode.
+    pub fn copy_selection(&mut self) {
+        let Some((rows, cols)) = self.selection() else {
+            return;
+        };
+        let rows: Vec<usize> = rows.filter(|row| self.is_shown(*row)).collect();
+        self.register = rows
+            .into_iter()
+            .map(|row| self.row(row)[cols.clone()].to_vec())
+            .collect();
+        self.anchor = None;
+        self.status = Some(Status::Info(format!(
+            "Copied {} by {} cells",
+            self.register.len(),
+            cols.len()
+        )));
+    }
+    /// Copies the selected cells into the register, then clears them.
+    pub fn cut_selection(&mut self) {
+        let selection = self.selection();
+        self.copy_selection();
+        if let Some((rows, cols)) = selection {
+            self.clear_cells(rows.clone(), cols.clone());
+            self.status = Some(Status::Info(format!(
+                "Cut {} by {} cells",
+                self.register.len(),
+                cols.len()
+            )));
+        }
+    }
+    /// Empties the selected cells and leaves selection mode.
+    pub fn clear_selection(&mut self) {
+        if let Some((rows, cols)) = self.selection() {
+            self.anchor = None;
+            self.clear_cells(rows, cols);
+        }
+    }
+    /// Empties the cells in `rows` and `cols` as one undo step, leaving the cursor on the first.
+    fn clear_cells(&mut self, rows: Range<usize>, cols: Range<usize>) {
+        if self.read_only {
+            self.refuse_read_only();
+            return;
+        }
+        let mut operations = Vec::new();
+        let shown: Vec<usize> = rows.clone().filter(|row| self.is_shown(*row)).collect();
+        for row in shown {
+            for (col, old) in self.row(row).into_iter().enumerate() {
+                if cols.contains(&col) && !old.is_empty() {
+                    operations.push(Operation::EditCell {
+                        row,
+                        col,
+                        old,
+                        new: String::new(),
+                    });
+                }
+            }
+        }
+        if !operations.is_empty() {
+            self.perform(Operation::Group(operations));
+        }
+        self.move_to(rows.start, cols.start);
+    }
+    /// Writes the matrix back to the file, reporting failures in the path bar.
+    pub fn save(&mut self) {
+        match self.write_file() {
+            Ok(()) => self.dirty = false,
+            Err(err) => self.status = Some(Status::Error(format!("Save failed: {err}"))),
+        }
+    }
+    fn write_file(&mut self) -> AppResult<()> {
+        let path = Path::new(&self.path);
+        if self.backup && !self.backed_up {
+            fs::copy(path, file::backup_path(path))?;
+            self.backed_up = true;
+        }
+        if let Some(stream) = &mut self.stream {
+            file::write_atomic(path, |out| stream.write_to(out, &self.format))?;
+            stream.reopen()?;
+            return Ok(());
+        }
+        let contents = csv::write(&self.value_matrix, &self.quoted_cells, &self.format);
+        file::write_atomic(path, |out| out.write_all(contents.as_bytes()))?;
+        if self.persist_history {
+            if let Err(err) = self.history.store(path, contents.as_bytes()) {
+                self.status = Some(Status::Error(format!("Undo history not saved: {err}")));
+            }
+        }
+        Ok(())
+    }
+    /// Inserts an empty row below the cursor, or above it, and moves onto it.
+    pub fn insert_row(&mut self, below: bool) {
+        if !self.in_memory() {
+            return;
+        }
+        let (col, row) = self.current_location;
+        let index = row + below as usize;
+        self.perform(Operation::InsertRow {
+            index,
+            cells: vec![String::new(); self.value_matrix[0].len()],
+        });
+        self.move_to(index, col);
+    }
+    /// Deletes the row under the cursor, keeping the cursor on the row that takes its place.
+    pub fn delete_row(&mut self) {
+        if self.in_memory() && self.value_matrix.len() > 1 {
+            let index = self.current_location.1;
+            self.perform(Operation::DeleteRow {
+                index,
+                cells: self.value_matrix[index].clone(),
+            });
+        }
+    }
+    /// Inserts an empty column right of the cursor, or left of it, and moves onto it.
+    pub fn insert_col(&mut self, right: bool) {
+        if !self.in_memory() {
+            return;
+        }
+        let (col, row) = self.current_location;
+        let index = col + right as usize;
+        self.perform(Operation::InsertCol {
+            index,
+            cells: vec![String::new(); self.value_matrix.len()],
+        });
+        self.move_to(row, index);
+    }
+    /// Deletes the column under the cursor, keeping the cursor on the column that takes its place.
+    pub fn delete_col(&mut self) {
+        if self.in_memory() && self.value_matrix[0].len() > 1 {
+            let index = self.current_location.0;
+            self.perform(Operation::DeleteCol {
+                index,
+                cells: self
+                    .value_matrix
+                    .iter()
+                    .map(|row| row[index].clone())
+                    .collect(),
+            });
+        }
+    }
+    /// Swaps the row under the cursor with the one shown below, or above,
+    /// keeping the header row in place.
+    pub fn move_row(&mut self, down: bool) {
+        if !self.in_memory() {
+            return;
+        }
+        let header_rows = self.has_header_row as usize;
+        let (col, row) = self.current_location;
+        let i = self.view_index(row);
+        let Some(target) = (match down {
+            true => Some(i + 1).filter(|i| *i < self.shown_rows()),
+            false => i.checked_sub(1),
+        })
+        .map(|i| self.view_row(i))
+        .filter(|target| row.min(*target) >= header_rows) else {
+            return;
+        };
+        let mut order: Vec<usize> = (0..self.value_matrix.len()).collect();
+        order.swap(row, target);
+        self.perform(Operation::ReorderRows { order });
+        self.move_to(target, col);
+    }
+    /// Swaps the column under the cursor with the one to its right, or left.
+    pub fn move_col(&mut self, right: bool) {
+        if !self.in_memory() {
+            return;
+        }
+        let (col, row) = self.current_location;
+        let cols = self.value_matrix[0].len();
+        let Some(target) = (match right {
+            true => Some(col + 1).filter(|target| *target < cols),
+            false => col.checked_sub(1),
+        }) else {
+            return;
+        };
+        let mut order: Vec<usize> = (0..cols).collect();
+        order.swap(col, target);
+        self.perform(Operation::ReorderCols { order });
+        self.move_to(row, target);
+    }
+    /// Opens the dialog listing the columns to put them in a new order.
+    pub fn open_reorder(&mut self) {
+        if self.in_memory() {
+            self.anchor = None;
+            self.reordering = Some(ColumnOrder::new(
+                self.value_matrix[0].len(),
+                self.current_location.0,
+            ));
+        }
+    }
+    /// Puts the columns in the order chosen in the reorder dialog, as one undoable step.
+    pub fn finish_reorder(&mut self) {
+        let Some(reorder) = self.reordering.take() else {
+            return;
+        };
+        if reorder.is_changed() {
+            let (col, row) = self.current_location;
+            let moved = reorder.order.iter().position(|j| *j == col).unwrap_or(col);
+            self.perform(Operation::ReorderCols {
+                order: reorder.order,
+            });
+            self.move_to(row, moved);
+            self.status = Some(Status::Info("Reordered columns".to_string()));
+        }
+    }
+    pub fn cancel_reorder(&mut self) {
+        self.reordering = None;
+    }
+    /// Picks up the column listed at screen row `y` of the reorder dialog to drag it.
+    pub fn grab_col(&mut self, y: u16) {
+        if let Some(reorder) = &mut self.reordering {
+            if let Some(place) = reorder.place_at(y) {
+                reorder.grabbed = false;
+                reorder.move_to(place);
+                reorder.grabbed = true;
+            }
+        }
+    }
+    /// Drags the grabbed column in the reorder dialog to screen row `y`.
+    pub fn drag_col(&mut self, y: u16) {
+        if let Some(reorder) = &mut self.reordering {
+            let place = match reorder.place_at(y) {
+                Some(place) => place,
+                None if y < reorder.area.y => reorder.top.saturating_sub(1),
+                None => reorder.top + reorder.area.height as usize,
+            };
+            reorder.move_to(place);
+        }
+    }
+    pub fn drop_col(&mut self) {
+        if let Some(reorder) = &mut self.reordering {
+            reorder.grabbed = false;
+        }
+    }
+    /// Sorts the rows below the header by `col`, descending if it was just sorted ascending.
+    pub fn sort_by_col(&mut self, col: usize) {
+        let descending = self.sorted_by == Some((col, false));
+        self.sort(&[(col, descending)]);
+    }
+    /// Sorts the rows below the header by the column under the cursor.
+    pub fn sort_current(&mut self, descending: bool) {
+        self.sort(&[(self.current_location.0, descending)]);
+    }
+    /// Sorts the rows below the header by each of `keys` in turn, a column
+    /// and whether descending, as one undoable step. Columns are compared
+    /// by the type of their cells, cells that don't fit it and then blank
+    /// cells go last either way, and rows that compare equal keep their
+    /// order.
+    pub fn sort(&mut self, keys: &[(usize, bool)]) {
+        if !self.in_memory() || keys.is_empty() {
+            return;
+        }
+        let header_rows = self.has_header_row as usize;
+        let types: Vec<ColumnType> = keys.iter().map(|(col, _)| self.col_type(*col)).collect();
+        let mut order: Vec<usize> = (0..self.value_matrix.len()).collect();
+        order[header_rows..].sort_by(|a, b| {
+            keys.iter()
+                .zip(&types)
+                .map(|((col, descending), kind)| {
+                    let (a, b) = (&self.value_matrix[*a][*col], &self.value_matrix[*b][*col]);
+                    let rank = |cell: &str| match cell.trim().is_empty() {
+                        true => 2,
+                        false => !kind.fits(cell) as u8,
+                    };
+                    rank(a).cmp(&rank(b)).then_with(|| match descending {
+                        true => kind.compare(a, b).reverse(),
+                        false => kind.compare(a, b),
+                    })
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or(Ordering::Equal)
+        });
+        if order.iter().enumerate().any(|(i, j)| i != *j) {
+            self.perform(Operation::ReorderRows { order });
+        }
+        self.sorted_by = match keys {
+            [key] => Some(*key),
+            _ => None,
+        };
+        let keys: Vec<String> = keys
+            .iter()
+            .map(|(col, descending)| {
+                format!(
+                    "{} {}",
+                    goto::col_letters(*col),
+                    match descending {
+                        true => "descending",
+                        false => "ascending",
                     }
-                }),
-        );
+                )
+            })
+            .collect();
+        self.status = Some(Status::Info(format!("Sorted by {}", keys.join(", "))));
     }
-    pub fn add_row(&mut self) {
-        self.save(true);
-        self.value_matrix
-            .push(vec![" ".to_string(); self.value_matrix[0].len()]);
+    /// Opens the prompt for the columns to sort by, starting from the column under the cursor.
+    pub fn open_sort(&mut self) {
+        if self.in_memory() {
+            let col = goto::col_letters(self.current_location.0);
+            self.open_prompt(PromptKind::Sort, col);
+        }
+    }
+    /// Looks up the columns of `keys` and sorts by them.
+    fn sort_by_keys(&mut self, keys: SortKeys) {
+        let mut resolved = Vec::new();
+        for key in keys.0 {
+            match self.find_col(&key.col) {
+                Some(col) => resolved.push((col, key.descending)),
+                None => {
+                    self.status = Some(Status::Error(format!("No column `{}`", key.col)));
+                    return;
+                }
+            }
+        }
+        self.sort(&resolved);
     }
-    pub fn remove_row(&mut self) {
-        if self.value_matrix.len() != 0 {
-            self.current_location = (0, 0);
-            self.save(true);
-            self.value_matrix.pop();
+    /// Opens the prompt for the conditions rows have to meet to be shown,
+    /// starting from the filter applied last.
+    pub fn open_filter(&mut self) {
+        if self.loaded() {
+            let text = match &self.filter {
+                Some(filter) => filter.text.clone(),
+                None => format!("{} = ", goto::col_letters(self.current_location.0)),
+            };
+            self.open_prompt(PromptKind::Filter, text);
         }
     }
-    pub fn add_col(&mut self) {
-        self.save(true);
-        for row in &mut self.value_matrix {
-            row.push(String::new());
+    /// Shows only the rows below the header that meet every condition in `text`.
+    fn apply_filter(&mut self, text: &str) {
+        let conditions = match text.parse::<Conditions>() {
+            Ok(conditions) => conditions,
+            Err(err) => {
+                self.status = Some(Status::Error(err));
+                return;
+            }
+        };
+        let mut predicates = Vec::new();
+        for condition in conditions.0 {
+            match self.find_col(&condition.col) {
+                Some(col) => predicates.push((col, condition.predicate)),
+                None => {
+                    self.status = Some(Status::Error(format!("No column `{}`", condition.col)));
+                    return;
+                }
+            }
+        }
+        let header_rows = self.has_header_row as usize;
+        let rows: Vec<usize> = (0..self.value_matrix.len())
+            .filter(|row| {
+                *row < header_rows
+                    || predicates
+                        .iter()
+                        .all(|(col, predicate)| predicate.test(&self.value_matrix[*row][*col]))
+            })
+            .collect();
+        if rows.len() == header_rows {
+            self.status = Some(Status::Info("No rows match the filter".to_string()));
+            return;
+        }
+        self.filter = Some(Filter {
+            text: text.trim().to_string(),
+            rows,
+            header_rows,
+        });
+        self.row_offset = 0;
+        if self.search.is_active() {
+            self.run_search();
         }
+        let (col, row) = self.current_location;
+        self.move_to(row, col);
+        self.report_filter();
     }
-    pub fn remove_col(&mut self) {
-        if self.value_matrix[0].len() != 0 {
-            self.current_location = (0, 0);
-            self.save(true);
-            for row in &mut self.value_matrix {
-                row.pop();
+    /// Shows every row again.
+    pub fn clear_filter(&mut self) {
+        if self.filter.take().is_some() {
+            if self.search.is_active() {
+                self.run_search();
             }
+            self.status = Some(Status::Info("Showing all rows".to_string()));
+        }
+    }
+    /// Number of rows below the header shown, and in the whole table.
+    pub fn filtered_rows(&self) -> Option<(usize, usize)> {
+        let header_rows = self.has_header_row as usize;
+        let filter = self.filter.as_ref()?;
+        let shown = filter
+            .rows
+            .iter()
+            .filter(|row| **row >= header_rows)
+            .count();
+        Some((shown, self.row_count().saturating_sub(header_rows)))
+    }
+    fn report_filter(&mut self) {
+        if let Some((shown, total)) = self.filtered_rows() {
+            self.status = Some(Status::Info(format!("Showing {shown} of {total} rows")));
         }
     }
+    /// Opens the search prompt, starting from the last query.
+    pub fn open_search(&mut self) {
+        if self.loaded() {
+            self.open_prompt(PromptKind::Search, self.search.query.clone());
+            self.prompt_changed();
+        }
+    }
+    /// Catches up with what was typed into the prompt.
+    ///
+    /// Searches run as the query is typed, moving to the first match from
+    /// where the prompt was opened.
+    pub fn prompt_changed(&mut self) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::Search | PromptKind::Find => {
+                let (col, row) = prompt.origin;
+                self.search.query = prompt.editor.value.clone();
+                self.run_search();
+                let (row, col) = self.search.at_or_after((row, col)).unwrap_or((row, col));
+                self.move_to(row, col);
+            }
+            PromptKind::Replace => self.run_search(),
+            PromptKind::Goto | PromptKind::Sort | PromptKind::Filter => {}
+        }
+    }
+    /// Closes the prompt, acting on what was typed.
+    pub fn submit_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::Search => self.report_matches(),
+            PromptKind::Find if self.search.matches.is_empty() => self.report_matches(),
+            PromptKind::Find => {
+                self.prompt = Some(Prompt::new(
+                    PromptKind::Replace,
+                    String::new(),
+                    prompt.origin,
+                    prompt.selection,
+                ))
+            }
+            PromptKind::Replace => self.start_replace(prompt.editor.value, prompt.origin, false),
+            PromptKind::Goto if prompt.editor.value.trim().is_empty() => {}
+            PromptKind::Goto => match prompt.editor.value.parse() {
+                Ok(target) => self.go_to(target),
+                Err(err) => self.status = Some(Status::Error(err)),
+            },
+            PromptKind::Filter if prompt.editor.value.trim().is_empty() => self.clear_filter(),
+            PromptKind::Filter => self.apply_filter(&prompt.editor.value),
+            PromptKind::Sort if prompt.editor.value.trim().is_empty() => {}
+            PromptKind::Sort => match prompt.editor.value.parse() {
+                Ok(keys) => self.sort_by_keys(keys),
+                Err(err) => self.status = Some(Status::Error(err)),
+            },
+        }
+    }
+    /// Closes the prompt, undoing whatever it did while typing.
+    pub fn cancel_prompt(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::Search | PromptKind::Find | PromptKind::Replace => {
+                self.search.clear();
+                let (col, row) = prompt.origin;
+                self.move_to(row, col);
+            }
+            PromptKind::Goto | PromptKind::Sort | PromptKind::Filter => {}
+        }
+    }
+    /// Opens the prompt for a cell, row or column to move to.
+    pub fn open_goto(&mut self) {
+        self.open_prompt(PromptKind::Goto, String::new());
+    }
+    /// Opens a prompt for `kind` starting from `value`, leaving selection
+    /// mode. Searches opened on a selection look only inside it.
+    fn open_prompt(&mut self, kind: PromptKind, value: String) {
+        let selection = self.selection();
+        self.anchor = None;
+        if kind.searches() {
+            self.search.scope = match &selection {
+                Some((rows, cols)) => Scope::Selection {
+                    rows: rows.clone(),
+                    cols: cols.clone(),
+                },
+                None if matches!(self.search.scope, Scope::Selection { .. }) => Scope::Sheet,
+                None => self.search.scope.clone(),
+            };
+        }
+        self.prompt = Some(Prompt::new(kind, value, self.current_location, selection));
+    }
+    /// Moves the cursor to `target`, telling the user when it is not in the table.
+    fn go_to(&mut self, target: Target) {
+        let (col, row) = self.current_location;
+        let (row, col) = match target {
+            Target::Cell(row, col) => (row, col),
+            Target::Row(row) => (row, col),
+            Target::Col(col) => (row, col),
+            Target::Header(name) => match self.header_col(&name) {
+                Some(col) => (row, col),
+                None => {
+                    self.status = Some(Status::Error(format!("No column named `{name}`")));
+                    return;
+                }
+            },
+        };
+        if row >= self.row_count() || col >= self.col_count() {
+            self.status = Some(Status::Error(format!(
+                "The table has {} rows and {} columns",
+                self.row_count(),
+                self.col_count()
+            )));
+            return;
+        }
+        if !self.is_shown(row) {
+            self.status = Some(Status::Error(format!(
+                "Row {} is hidden by the filter",
+                row + 1
+            )));
+            return;
+        }
+        self.move_to(row, col);
+    }
+    /// Column named by a number counting from one, a header or spreadsheet letters.
+    fn find_col(&mut self, name: &str) -> Option<usize> {
+        match name.parse::<usize>() {
+            Ok(n) => n.checked_sub(1).filter(|col| *col < self.col_count()),
+            Err(_) => self.header_col(name),
+        }
+    }
+    /// Column whose header is `name`, ignoring case if nothing matches
+    /// exactly, or else the column named by spreadsheet letters.
+    fn header_col(&mut self, name: &str) -> Option<usize> {
+        if self.has_header_row {
+            let headers = self.row(0);
+            let found = headers
+                .iter()
+                .position(|header| header.trim() == name)
+                .or_else(|| {
+                    headers
+                        .iter()
+                        .position(|header| header.trim().eq_ignore_ascii_case(name))
+                });
+            if found.is_some() {
+                return found;
+            }
+        }
+        goto::letters(name).filter(|col| *col < self.col_count())
+    }
+    /// Opens the find and replace prompts, starting from the last query.
+    pub fn open_replace(&mut self) {
+        if self.in_memory() {
+            self.open_prompt(PromptKind::Find, self.search.query.clone());
+            self.prompt_changed();
+        }
+    }
+    /// Replaces every match at once from the replacement prompt.
+    pub fn replace_all(&mut self) {
+        if let Some(prompt) = self
+            .prompt
+            .take_if(|prompt| prompt.kind == PromptKind::Replace)
+        {
+            self.start_replace(prompt.editor.value, prompt.origin, true);
+        }
+    }
+    /// Starts replacing the matches with `replacement` from `origin`, all
+    /// at once or asking about each in turn. Everything replaced until
+    /// [`App::finish_replace`] is undone as one step.
+    fn start_replace(&mut self, replacement: String, origin: (usize, usize), all: bool) {
+        let (col, row) = origin;
+        let Some(replace) = Replace::new(&self.search, replacement, (row, col)) else {
+            self.report_matches();
+            return;
+        };
+        self.begin_group();
+        self.replacing = Some(replace);
+        match all {
+            true => self.replace_rest(),
+            false => self.next_replacement(),
+        }
+    }
+    /// Moves to the next match to ask about, finishing when there are none left.
+    fn next_replacement(&mut self) {
+        match self.replacing.as_ref().and_then(|r| r.pending.front()) {
+            Some((row, col)) => self.move_to(*row, *col),
+            None => self.finish_replace(),
+        }
+    }
+    /// Replaces the match under the cursor and moves to the next one.
+    pub fn replace_one(&mut self) {
+        let Some(replace) = &mut self.replacing else {
+            return;
+        };
+        if let Some((row, col)) = replace.pending.pop_front() {
+            let old = &self.value_matrix[row][col];
+            let new = replace.apply(old);
+            if *old != new {
+                replace.replaced += 1;
+                let old = old.clone();
+                let operation = Operation::EditCell { row, col, old, new };
+                self.perform(operation.clone());
+                // Each replacement shows as it is made, not when the group ends.
+                self.refresh(Some(&operation));
+            }
+        }
+        self.next_replacement();
+    }
+    /// Leaves the match under the cursor as it is and moves to the next one.
+    pub fn skip_replace(&mut self) {
+        if let Some(replace) = &mut self.replacing {
+            replace.pending.pop_front();
+        }
+        self.next_replacement();
+    }
+    /// Replaces every match not yet asked about, then finishes.
+    pub fn replace_rest(&mut self) {
+        let Some(replace) = &mut self.replacing else {
+            return;
+        };
+        let mut operations = Vec::new();
+        for (row, col) in std::mem::take(&mut replace.pending) {
+            let old = &self.value_matrix[row][col];
+            let new = replace.apply(old);
+            if *old != new {
+                operations.push(Operation::EditCell {
+                    row,
+                    col,
+                    old: old.clone(),
+                    new,
+                });
+            }
+        }
+        replace.replaced += operations.len();
+        if !operations.is_empty() {
+            self.perform(Operation::Group(operations));
+        }
+        self.finish_replace();
+    }
+    /// Stops replacing, recording everything replaced as one undo step.
+    pub fn finish_replace(&mut self) {
+        if let Some(replace) = self.replacing.take() {
+            self.end_group();
+            self.status = Some(Status::Info(match replace.replaced {
+                1 => "Replaced 1 cell".to_string(),
+                n => format!("Replaced {n} cells"),
+            }));
+        }
+    }
+    pub fn toggle_search_case(&mut self) {
+        self.search.case_sensitive = !self.search.case_sensitive;
+        self.prompt_changed();
+    }
+    pub fn toggle_search_regex(&mut self) {
+        self.search.regex = !self.search.regex;
+        self.prompt_changed();
+    }
+    /// Cycles the search through the whole sheet, the column the prompt
+    /// was opened on and the selection it was opened with.
+    pub fn toggle_search_scope(&mut self) {
+        let Some(prompt) = &self.prompt else {
+            return;
+        };
+        self.search.scope = match (&self.search.scope, &prompt.selection) {
+            (Scope::Sheet, _) => Scope::Column(prompt.origin.0),
+            (Scope::Column(_), Some((rows, cols))) => Scope::Selection {
+                rows: rows.clone(),
+                cols: cols.clone(),
+            },
+            _ => Scope::Sheet,
+        };
+        self.prompt_changed();
+    }
+    /// Finds the cells that match the search, leaving out rows the filter hides.
+    fn run_search(&mut self) {
+        self.search.run(&self.value_matrix);
+        if let Some(filter) = &self.filter {
+            self.search.matches.retain(|(row, _)| filter.shows(*row));
+        }
+    }
+    pub fn next_match(&mut self) {
+        let (col, row) = self.current_location;
+        match self.search.after((row, col)) {
+            Some((row, col)) => self.move_to(row, col),
+            None => self.report_matches(),
+        }
+    }
+    pub fn prev_match(&mut self) {
+        let (col, row) = self.current_location;
+        match self.search.before((row, col)) {
+            Some((row, col)) => self.move_to(row, col),
+            None => self.report_matches(),
+        }
+    }
+    /// Stops highlighting the matches of the last search.
+    pub fn clear_search(&mut self) {
+        self.search.clear();
+    }
+    /// Tells the user how many cells the search found.
+    fn report_matches(&mut self) {
+        self.status = Some(match (self.search.invalid, self.search.matches.len()) {
+            (true, _) => Status::Error("Invalid pattern".to_string()),
+            (false, 0) => Status::Info("No matches".to_string()),
+            (false, 1) => Status::Info("1 match".to_string()),
+            (false, n) => Status::Info(format!("{n} matches")),
+        });
+    }
     pub fn toggle_graph_mode(&mut self) {
-        self.is_graph = !self.is_graph;
+        if self.in_memory() {
+            self.is_graph = !self.is_graph;
+        }
     }
     pub fn undo(&mut self) {
-        if let Some(j) = self.previous_matrices.pop() {
-            let _ = fs::write(self.path.clone(), j);
+        if let Some(operation) = self.history.undo() {
+            self.apply(&operation);
+        }
+    }
+    pub fn redo(&mut self) {
+        if let Some(operation) = self.history.redo() {
+            self.apply(&operation);
         }
-        self.update_curr();
-        self.save(false);
     }
     pub fn toggle_controls(&mut self) {
         self.controls = !self.controls;
     }
 }
+
+/// Parses the contents of a csv file.
+///
+/// The delimiter is sniffed from the contents unless one is given.
+/// Short records are padded with empty cells so every row is as wide as the
+/// widest one, and an empty file yields a single empty cell.
+fn load(file: &str, delimiter: Option<char>) -> csv::Document {
+    let delimiter = delimiter.unwrap_or_else(|| csv::sniff_delimiter(file));
+    let mut document = csv::parse(file, delimiter);
+    let width = document
+        .records
+        .iter()
+        .map(|row| row.len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    if document.records.is_empty() {
+        document.records.push(Vec::new());
+        document.quoted.push(Vec::new());
+    }
+    for row in &mut document.records {
+        row.resize(width, String::new());
+    }
+    for row in &mut document.quoted {
+        row.resize(width, false);
+    }
+    document
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delimiter_overrides_sniffing() {
+        let file = include_str!("../example-data/semicolon.csv");
+        assert_eq!(load(file, None).format.delimiter, ';');
+        let document = load(file, Some(','));
+        assert_eq!(document.format.delimiter, ',');
+        assert_eq!(document.records[1], ["01.03.2024;Berlin;4", "5;0", "0"]);
+    }
+
+    /// An app holding `rows` in memory, as if loaded from a file.
+    fn app(rows: &[&[&str]]) -> App {
+        let value_matrix: Vec<Vec<String>> = rows
+            .iter()
+            .map(|row| row.iter().map(|cell| cell.to_string()).collect())
+            .collect();
+        let mut app = App {
+            quoted_cells: vec![vec![false; rows[0].len()]; rows.len()],
+            value_matrix,
+            ..App::default()
+        };
+        app.infer_types();
+        app
+    }
+
+    #[test]
+    fn moving_columns_carries_their_types() {
+        let mut app = app(&[&["1", "a"], &["2", "b"]]);
+        app.perform(Operation::ReorderCols { order: vec![1, 0] });
+        assert_eq!(app.col_type(0), ColumnType::Text);
+        assert_eq!(app.col_type(1), ColumnType::Integer);
+        app.undo();
+        assert_eq!(app.col_type(0), ColumnType::Integer);
+    }
+
+    #[test]
+    fn leaves_untyped_columns_without_a_guess() {
+        let app = App::default();
+        assert!(app.col_guess(0).is_none());
+        assert_eq!(app.col_type(0), ColumnType::Text);
+    }
+
+    #[test]
+    fn refuses_edits_that_dont_fit_until_confirmed() {
+        let mut app = app(&[&["1"], &["2"]]);
+        app.enter_editing();
+        app.editor = LineEditor::new("x".to_string());
+        app.exit_editing();
+        assert!(app.editing);
+        assert_eq!(app.value_matrix[0][0], "1");
+        assert_eq!(app.col_type(0), ColumnType::Integer);
+        app.exit_editing();
+        assert!(!app.editing);
+        assert_eq!(app.value_matrix[0][0], "x");
+    }
+
+    #[test]
+    fn asks_before_quitting_with_a_refused_edit() {
+        let mut app = app(&[&["1"], &["2"]]);
+        app.enter_editing();
+        app.editor = LineEditor::new("x".to_string());
+        app.exit_editing();
+        app.request_quit();
+        assert!(app.running);
+        assert!(app.confirming_quit);
+    }
+
+    #[test]
+    fn undo_restores_the_matrix() {
+        let mut app = app(&[&["a", "1"], &["b", "2"], &["c", "3"]]);
+        let before = app.value_matrix.clone();
+        let operations = [
+            Operation::EditCell {
+                row: 1,
+                col: 1,
+                old: "2".to_string(),
+                new: "x".to_string(),
+            },
+            Operation::InsertRow {
+                index: 1,
+                cells: vec!["d".to_string(), "4".to_string()],
+            },
+            Operation::DeleteRow {
+                index: 0,
+                cells: before[0].clone(),
+            },
+            Operation::InsertCol {
+                index: 1,
+                cells: vec![String::new(); 3],
+            },
+            Operation::DeleteCol {
+                index: 0,
+                cells: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            },
+            Operation::ReorderRows {
+                order: vec![2, 0, 1],
+            },
+            Operation::ReorderCols { order: vec![1, 0] },
+            Operation::Group(vec![
+                Operation::ReorderRows {
+                    order: vec![1, 2, 0],
+                },
+                Operation::EditCell {
+                    row: 0,
+                    col: 0,
+                    old: "b".to_string(),
+                    new: "y".to_string(),
+                },
+                Operation::InsertRow {
+                    index: 3,
+                    cells: vec![String::new(); 2],
+                },
+            ]),
+        ];
+        for operation in operations {
+            app.perform(operation.clone());
+            let after = app.value_matrix.clone();
+            assert_ne!(after, before, "{operation:?}");
+            app.undo();
+            assert_eq!(app.value_matrix, before, "{operation:?}");
+            app.redo();
+            assert_eq!(app.value_matrix, after, "{operation:?}");
+            app.undo();
+        }
+    }
+
+    #[test]
+    fn sorts_cells_that_dont_fit_last_both_ways() {
+        let cells = ["3", "10", "1", "x", "", "2", "9", "4", "8", "7", "5", "6"];
+        let rows: Vec<&[&str]> = cells.iter().map(std::slice::from_ref).collect();
+        let mut app = app(&rows);
+        assert_eq!(app.col_type(0), ColumnType::Integer);
+        let column = |app: &App| -> Vec<String> {
+            app.value_matrix.iter().map(|row| row[0].clone()).collect()
+        };
+        app.sort(&[(0, false)]);
+        assert_eq!(
+            column(&app),
+            ["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "x", ""]
+        );
+        app.sort(&[(0, true)]);
+        assert_eq!(
+            column(&app),
+            ["10", "9", "8", "7", "6", "5", "4", "3", "2", "1", "x", ""]
+        );
+    }
+
+    #[test]
+    fn filter_keeps_the_header_when_it_is_deleted() {
+        let mut app = app(&[&["S"], &["ok"], &["FAILED"], &["ok"], &["FAILED"]]);
+        app.has_header_row = true;
+        app.apply_filter("A = FAILED");
+        assert_eq!(app.filtered_rows(), Some((2, 4)));
+        app.perform(Operation::DeleteRow {
+            index: 0,
+            cells: vec!["S".to_string()],
+        });
+        let shown: Vec<usize> = (0..app.shown_rows()).map(|i| app.view_row(i)).collect();
+        assert_eq!(shown, [0, 1, 3]);
+        assert_eq!(app.filtered_rows(), Some((2, 3)));
+        app.undo();
+        assert_eq!(app.value_matrix[0], ["S"]);
+        assert!(app.is_shown(0));
+    }
+}
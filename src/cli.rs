@@ -0,0 +1,70 @@
+use std::env;
+
+use crate::{app::AppResult, csv::QuoteStyle};
+
+const USAGE: &str =
+    "usage: csv-reader [--backup] [--history] [--stream] [--read-only] [--freeze-cols <n>] [--delimiter <char>] [--quote minimal|all|non-numeric] <path>";
+
+/// Command line options.
+#[derive(Debug, Default)]
+pub struct Args {
+    /// Path to the csv file.
+    pub path: String,
+    /// Quoting policy used when saving.
+    pub quote_style: QuoteStyle,
+    /// Field delimiter, detected from the file when not given.
+    pub delimiter: Option<char>,
+    /// Keep a `~` backup of the file as it was before the first save.
+    pub backup: bool,
+    /// Keep the undo history next to the file between sessions.
+    pub history: bool,
+    /// Read rows on demand instead of loading the whole file.
+    pub stream: bool,
+    /// Memory-map the file for viewing without allowing edits.
+    pub read_only: bool,
+    /// Number of leading columns kept in view while scrolling sideways.
+    pub freeze_cols: usize,
+}
+
+impl Args {
+    /// Parses the arguments the program was started with.
+    pub fn parse() -> AppResult<Self> {
+        let mut args = Self::default();
+        let mut path = None;
+        let mut iter = env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--quote" => args.quote_style = value(&mut iter, &arg)?.parse()?,
+                "--backup" => args.backup = true,
+                "--history" => args.history = true,
+                "--stream" => args.stream = true,
+                "--read-only" => args.read_only = true,
+                "--freeze-cols" => args.freeze_cols = value(&mut iter, &arg)?.parse()?,
+                "--delimiter" => args.delimiter = Some(delimiter(&value(&mut iter, &arg)?)?),
+                _ if arg.starts_with("--") => return Err(format!("unknown option `{arg}`").into()),
+                _ => path = Some(arg),
+            }
+        }
+        args.path = path.ok_or(USAGE)?;
+        Ok(args)
+    }
+}
+
+/// Takes the value following the option `name`.
+fn value(iter: &mut impl Iterator<Item = String>, name: &str) -> AppResult<String> {
+    iter.next()
+        .ok_or_else(|| format!("missing value for `{name}`").into())
+}
+
+/// Reads a delimiter given as a single character, `tab` or `\t`.
+fn delimiter(value: &str) -> AppResult<char> {
+    let mut chars = value.chars();
+    match (value, chars.next(), chars.next()) {
+        ("tab" | "\\t", _, _) => Ok('\t'),
+        (_, Some('"' | '\r' | '\n'), None) => {
+            Err(format!("`{value}` cannot be used as a delimiter").into())
+        }
+        (_, Some(ch), None) => Ok(ch),
+        _ => Err(format!("delimiter must be a single character, got `{value}`").into()),
+    }
+}
@@ -0,0 +1,463 @@
+use crate::types;
+
+/// A parsed csv file along with what is needed to write it back unchanged.
+#[derive(Debug, Default)]
+pub struct Document {
+    /// Fields of every record.
+    pub records: Vec<Vec<String>>,
+    /// Whether each field was quoted in the source.
+    pub quoted: Vec<Vec<bool>>,
+    /// Line ending, quoting and layout used when writing.
+    pub format: Format,
+}
+
+/// When the writer puts quotes around a field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    /// Only fields containing a delimiter, quote or line break.
+    #[default]
+    Minimal,
+    /// Every field.
+    All,
+    /// Every non-empty field that is not a number.
+    NonNumeric,
+}
+
+impl std::str::FromStr for QuoteStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "minimal" => Ok(Self::Minimal),
+            "all" => Ok(Self::All),
+            "non-numeric" => Ok(Self::NonNumeric),
+            _ => Err(format!(
+                "unknown quote style `{s}`, expected minimal, all or non-numeric"
+            )),
+        }
+    }
+}
+
+/// How a [`Document`] is laid out on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Format {
+    /// Character separating fields.
+    pub delimiter: char,
+    /// Quoting policy for fields that were not quoted in the source.
+    pub quote_style: QuoteStyle,
+    /// Terminator written after each record.
+    pub line_ending: String,
+    /// Whether the last record is followed by a line ending.
+    pub trailing_newline: bool,
+}
+
+impl Default for Format {
+    fn default() -> Self {
+        Self {
+            delimiter: ',',
+            quote_style: QuoteStyle::Minimal,
+            line_ending: "\n".to_string(),
+            trailing_newline: true,
+        }
+    }
+}
+
+/// Delimiters recognised by [`sniff_delimiter`].
+pub const DELIMITERS: [char; 4] = [',', ';', '\t', '|'];
+
+/// Number of lines [`sniff_delimiter`] inspects.
+const SNIFF_LINES: usize = 20;
+
+/// Guesses which of [`DELIMITERS`] separates the fields of `input`.
+///
+/// Each candidate is tried on the first lines of the input and scored by
+/// how many records agree on the most common field count, then by that
+/// count. Candidates that never split a record lose, and a comma is
+/// assumed when none of them do.
+pub fn sniff_delimiter(input: &str) -> char {
+    let sample_end = input
+        .match_indices('\n')
+        .nth(SNIFF_LINES - 1)
+        .map_or(input.len(), |(i, _)| i);
+    let sample = &input[..sample_end];
+    let mut best = (',', (0, 0));
+    for delimiter in DELIMITERS {
+        let mut counts: Vec<usize> = parse(sample, delimiter)
+            .records
+            .iter()
+            .map(|record| record.len())
+            .collect();
+        // The last record may have been cut short by the sample.
+        if sample_end < input.len() {
+            counts.pop();
+        }
+        let width = counts
+            .iter()
+            .copied()
+            .max_by_key(|width| {
+                (
+                    counts.iter().filter(|count| *count == width).count(),
+                    *width,
+                )
+            })
+            .unwrap_or(0);
+        if width < 2 {
+            continue;
+        }
+        let score = (
+            counts.iter().filter(|count| **count == width).count(),
+            width,
+        );
+        if score > best.1 {
+            best = (delimiter, score);
+        }
+    }
+    best.0
+}
+
+/// Parses CSV text into records following RFC 4180.
+///
+/// Quoted fields may contain delimiters, line breaks and `""` escaped quotes.
+/// Records may end in `\n`, `\r\n` or a lone `\r`, and a trailing line
+/// break does not produce an extra empty record. A stray quote inside an
+/// unquoted field, or text following a closing quote, is kept literally
+/// instead of being rejected.
+///
+/// The first record terminator decides the line ending of the returned
+/// [`Format`].
+pub fn parse(input: &str, delimiter: char) -> Document {
+    let mut document = Document::default();
+    document.format.delimiter = delimiter;
+    let mut line_ending = None;
+    let mut record = Vec::new();
+    let mut quoted = Vec::new();
+    let mut field = String::new();
+    let mut field_quoted = false;
+    let mut chars = input.chars().peekable();
+    // Whether anything has been read for the current record yet.
+    let mut in_record = false;
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if field.is_empty() && !field_quoted => {
+                in_record = true;
+                field_quoted = true;
+                while let Some(ch) = chars.next() {
+                    match ch {
+                        '"' => match chars.peek() {
+                            Some('"') => {
+                                field.push('"');
+                                chars.next();
+                            }
+                            _ => break,
+                        },
+                        _ => field.push(ch),
+                    }
+                }
+            }
+            _ if ch == delimiter => {
+                in_record = true;
+                record.push(std::mem::take(&mut field));
+                quoted.push(std::mem::take(&mut field_quoted));
+            }
+            '\r' | '\n' => {
+                let terminator = if ch == '\r' && chars.peek() == Some(&'\n') {
+                    chars.next();
+                    "\r\n"
+                } else if ch == '\r' {
+                    "\r"
+                } else {
+                    "\n"
+                };
+                line_ending.get_or_insert(terminator);
+                record.push(std::mem::take(&mut field));
+                quoted.push(std::mem::take(&mut field_quoted));
+                document.records.push(std::mem::take(&mut record));
+                document.quoted.push(std::mem::take(&mut quoted));
+                in_record = false;
+            }
+            _ => {
+                in_record = true;
+                field.push(ch);
+            }
+        }
+    }
+    if in_record {
+        record.push(field);
+        quoted.push(field_quoted);
+        document.records.push(record);
+        document.quoted.push(quoted);
+    }
+    document.format.trailing_newline = !in_record && !document.records.is_empty();
+    if let Some(line_ending) = line_ending {
+        document.format.line_ending = line_ending.to_string();
+    }
+    document
+}
+
+/// Serializes `records` back into CSV text.
+///
+/// Fields marked in `quoted` are always quoted so untouched cells keep
+/// their source form, the rest follow [`Format::quote_style`]. Fields
+/// holding a delimiter, quote or line break are quoted regardless.
+pub fn write(records: &[Vec<String>], quoted: &[Vec<bool>], format: &Format) -> String {
+    let mut out = String::new();
+    for (i, record) in records.iter().enumerate() {
+        if i > 0 {
+            out.push_str(&format.line_ending);
+        }
+        for (j, field) in record.iter().enumerate() {
+            if j > 0 {
+                out.push(format.delimiter);
+            }
+            let was_quoted = quoted
+                .get(i)
+                .and_then(|row| row.get(j))
+                .copied()
+                .unwrap_or(false);
+            if was_quoted || needs_quotes(field, format) {
+                out.push('"');
+                out.push_str(&field.replace('"', "\"\""));
+                out.push('"');
+            } else {
+                out.push_str(field);
+            }
+        }
+    }
+    if format.trailing_newline && !records.is_empty() {
+        out.push_str(&format.line_ending);
+    }
+    out
+}
+
+/// Whether `field` has to be quoted when written with `format`.
+fn needs_quotes(field: &str, format: &Format) -> bool {
+    if field.contains([format.delimiter, '"', '\n', '\r']) {
+        return true;
+    }
+    match format.quote_style {
+        QuoteStyle::Minimal => false,
+        QuoteStyle::All => true,
+        QuoteStyle::NonNumeric => !field.is_empty() && types::parse_number(field.trim()).is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CRLF: &str = include_str!("../example-data/crlf.csv");
+    const QUOTED: &str = include_str!("../example-data/quoted.csv");
+
+    fn records(rows: &[&[&str]]) -> Vec<Vec<String>> {
+        rows.iter()
+            .map(|row| row.iter().map(|field| field.to_string()).collect())
+            .collect()
+    }
+
+    fn round_trip(input: &str) -> String {
+        let document = parse(input, ',');
+        write(&document.records, &document.quoted, &document.format)
+    }
+
+    #[test]
+    fn parses_crlf_fixture() {
+        let document = parse(CRLF, ',');
+        assert_eq!(
+            document.records,
+            records(&[
+                &["Name", "City", "Comment"],
+                &["Smith, Jane", "Boston", "said \"hi\""],
+                &["Doe", "New\r\nYork", ""],
+            ])
+        );
+        assert_eq!(
+            document.quoted,
+            [
+                vec![false, false, false],
+                vec![true, false, true],
+                vec![false, true, false],
+            ]
+        );
+        assert_eq!(document.format.line_ending, "\r\n");
+        assert!(document.format.trailing_newline);
+    }
+
+    #[test]
+    fn parses_quoted_fixture() {
+        let document = parse(QUOTED, ',');
+        assert_eq!(
+            document.records,
+            records(&[
+                &["Account", "Description", "Amount", "Notes"],
+                &["1001", "Office supplies, misc", "125.50", "Paid by card"],
+                &["1002", "Consultant \"rush\" fee", "2400.00", ""],
+                &[
+                    "1003",
+                    "Rent\nMarch and April",
+                    "3100.00",
+                    "Split over\ntwo invoices"
+                ],
+                &["1004", "Plain text", "0.00", ""],
+            ])
+        );
+        assert_eq!(document.quoted[4], [false, false, false, true]);
+        assert_eq!(document.format.line_ending, "\n");
+        assert!(document.format.trailing_newline);
+    }
+
+    #[test]
+    fn parses_escaped_quotes() {
+        let document = parse("\"a \"\"b\"\" c\",\"\"\"\"\n", ',');
+        assert_eq!(document.records, records(&[&["a \"b\" c", "\""]]));
+    }
+
+    #[test]
+    fn keeps_stray_quotes_literally() {
+        let document = parse("a\"b,\"c\"d\n", ',');
+        assert_eq!(document.records, records(&[&["a\"b", "cd"]]));
+    }
+
+    #[test]
+    fn parses_line_endings() {
+        for line_ending in ["\n", "\r\n", "\r"] {
+            let input = ["a,b", "c,d", ""].join(line_ending);
+            let document = parse(&input, ',');
+            assert_eq!(document.records, records(&[&["a", "b"], &["c", "d"]]));
+            assert_eq!(document.format.line_ending, line_ending);
+        }
+    }
+
+    #[test]
+    fn parses_without_trailing_newline() {
+        let with = parse("a,b\nc,d\n", ',');
+        let without = parse("a,b\nc,d", ',');
+        assert_eq!(with.records, without.records);
+        assert!(with.format.trailing_newline);
+        assert!(!without.format.trailing_newline);
+    }
+
+    #[test]
+    fn parses_empty_input() {
+        let document = parse("", ',');
+        assert!(document.records.is_empty());
+        assert!(!document.format.trailing_newline);
+    }
+
+    #[test]
+    fn round_trips_fixtures() {
+        assert_eq!(round_trip(CRLF), CRLF);
+        assert_eq!(round_trip(QUOTED), QUOTED);
+    }
+
+    #[test]
+    fn round_trips_layouts() {
+        for input in [
+            "a,b\nc,d\n",
+            "a,b\nc,d",
+            "a,b\r\nc,d\r\n",
+            "a,b\r\nc,d",
+            "\"a\nb\",\"\"\"\",,\"\"\n",
+        ] {
+            assert_eq!(round_trip(input), input);
+        }
+    }
+
+    fn format(quote_style: QuoteStyle) -> Format {
+        Format {
+            quote_style,
+            ..Format::default()
+        }
+    }
+
+    #[test]
+    fn writes_each_quote_style() {
+        let rows = records(&[&["name", "12", "-3.5", "", "inf"]]);
+        let expected = [
+            (QuoteStyle::Minimal, "name,12,-3.5,,inf\n"),
+            (QuoteStyle::All, "\"name\",\"12\",\"-3.5\",\"\",\"inf\"\n"),
+            (QuoteStyle::NonNumeric, "\"name\",12,-3.5,,\"inf\"\n"),
+        ];
+        for (quote_style, text) in expected {
+            assert_eq!(write(&rows, &[], &format(quote_style)), text);
+        }
+    }
+
+    #[test]
+    fn quotes_fields_that_need_it() {
+        let rows = records(&[&["a,b", "say \"hi\"", "two\nlines", "cr\r", "plain"]]);
+        assert_eq!(
+            write(&rows, &[], &format(QuoteStyle::Minimal)),
+            "\"a,b\",\"say \"\"hi\"\"\",\"two\nlines\",\"cr\r\",plain\n"
+        );
+        let format = Format {
+            delimiter: ';',
+            ..format(QuoteStyle::Minimal)
+        };
+        assert_eq!(
+            write(&records(&[&["a,b", "c;d"]]), &[], &format),
+            "a,b;\"c;d\"\n"
+        );
+    }
+
+    #[test]
+    fn keeps_quoted_cells_quoted() {
+        let rows = records(&[&["1", "text", ""]]);
+        let quoted = [vec![true, false, true]];
+        assert_eq!(
+            write(&rows, &quoted, &format(QuoteStyle::Minimal)),
+            "\"1\",text,\"\"\n"
+        );
+        assert_eq!(
+            write(&rows, &quoted, &format(QuoteStyle::NonNumeric)),
+            "\"1\",\"text\",\"\"\n"
+        );
+    }
+
+    #[test]
+    fn writes_line_ending_and_trailing_newline() {
+        let rows = records(&[&["a", "b"], &["c", "d"]]);
+        for line_ending in ["\n", "\r\n", "\r"] {
+            for trailing_newline in [true, false] {
+                let format = Format {
+                    line_ending: line_ending.to_string(),
+                    trailing_newline,
+                    ..Format::default()
+                };
+                let text = write(&rows, &[], &format);
+                let mut expected = ["a,b", "c,d"].join(line_ending);
+                if trailing_newline {
+                    expected.push_str(line_ending);
+                }
+                assert_eq!(text, expected);
+                assert_eq!(parse(&text, ',').format, format);
+            }
+        }
+    }
+
+    #[test]
+    fn sniffs_fixture_delimiters() {
+        assert_eq!(
+            sniff_delimiter(include_str!("../example-data/log.tsv")),
+            '\t'
+        );
+        assert_eq!(
+            sniff_delimiter(include_str!("../example-data/semicolon.csv")),
+            ';'
+        );
+        assert_eq!(sniff_delimiter(QUOTED), ',');
+    }
+
+    #[test]
+    fn ignores_delimiters_inside_quotes() {
+        // Split on every comma, each line would have two fields, as many as with `|`.
+        let input = "\"a,b\"|c\n\"1,2\"|x\n\"3,4\"|y\n";
+        assert_eq!(sniff_delimiter(input), '|');
+    }
+
+    #[test]
+    fn assumes_comma_without_delimiters() {
+        assert_eq!(sniff_delimiter("one\ntwo\n"), ',');
+        assert_eq!(sniff_delimiter(""), ',');
+    }
+}
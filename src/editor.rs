@@ -0,0 +1,273 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Text being edited on one line, with a cursor that moves a grapheme at a time.
+///
+/// The cursor is a byte offset that is always on a grapheme boundary, so
+/// accented letters, emoji and other multi-byte characters are moved over
+/// and deleted whole.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LineEditor {
+    /// The text.
+    pub value: String,
+    /// Byte offset of the cursor in `value`.
+    pub cursor: usize,
+}
+
+impl LineEditor {
+    /// Starts editing `value` with the cursor at the end.
+    pub fn new(value: String) -> Self {
+        let cursor = value.len();
+        Self { value, cursor }
+    }
+
+    /// Inserts `ch` before the cursor.
+    pub fn insert(&mut self, ch: char) {
+        self.value.insert(self.cursor, ch);
+        self.cursor += ch.len_utf8();
+    }
+
+    /// Inserts `text` before the cursor.
+    pub fn insert_str(&mut self, text: &str) {
+        self.value.insert_str(self.cursor, text);
+        self.cursor += text.len();
+    }
+
+    /// Deletes the grapheme before the cursor.
+    pub fn backspace(&mut self) {
+        let start = self.prev_boundary();
+        self.value.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Deletes the grapheme under the cursor.
+    pub fn delete(&mut self) {
+        let end = self.next_boundary();
+        self.value.drain(self.cursor..end);
+    }
+
+    pub fn move_left(&mut self) {
+        self.cursor = self.prev_boundary();
+    }
+    pub fn move_right(&mut self) {
+        self.cursor = self.next_boundary();
+    }
+    pub fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+    pub fn move_end(&mut self) {
+        self.cursor = self.value.len();
+    }
+    pub fn word_left(&mut self) {
+        self.cursor = self.word_start();
+    }
+    pub fn word_right(&mut self) {
+        self.cursor = self.word_end();
+    }
+
+    /// Moves to the same column on the line above, or to the start on the first line.
+    pub fn line_up(&mut self) {
+        let start = self.line_start(self.cursor);
+        if start == 0 {
+            self.cursor = 0;
+            return;
+        }
+        let column = self.value[start..self.cursor].graphemes(true).count();
+        self.cursor = self.column_offset(self.line_start(start - 1), column);
+    }
+    /// Moves to the same column on the line below, or to the end on the last line.
+    pub fn line_down(&mut self) {
+        let start = self.line_start(self.cursor);
+        let column = self.value[start..self.cursor].graphemes(true).count();
+        self.cursor = match self.value[self.cursor..].find('\n') {
+            Some(i) => self.column_offset(self.cursor + i + 1, column),
+            None => self.value.len(),
+        };
+    }
+
+    /// Deletes from the start of the word before the cursor up to the cursor.
+    pub fn kill_word(&mut self) {
+        let start = self.word_start();
+        self.value.drain(start..self.cursor);
+        self.cursor = start;
+    }
+
+    /// Deletes everything before the cursor.
+    pub fn kill_line(&mut self) {
+        self.value.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    /// The text before the cursor, the grapheme under it (empty at the end) and the text after it.
+    pub fn split(&self) -> (&str, &str, &str) {
+        let end = self.next_boundary();
+        (
+            &self.value[..self.cursor],
+            &self.value[self.cursor..end],
+            &self.value[end..],
+        )
+    }
+
+    /// Offset of the grapheme before the cursor.
+    fn prev_boundary(&self) -> usize {
+        self.value[..self.cursor]
+            .grapheme_indices(true)
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// Offset just past the grapheme under the cursor.
+    fn next_boundary(&self) -> usize {
+        self.value[self.cursor..]
+            .graphemes(true)
+            .next()
+            .map_or(self.cursor, |grapheme| self.cursor + grapheme.len())
+    }
+
+    /// Start of the line holding `offset`.
+    fn line_start(&self, offset: usize) -> usize {
+        self.value[..offset].rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    /// Offset `column` graphemes into the line starting at `start`, or the end of that line.
+    fn column_offset(&self, start: usize, column: usize) -> usize {
+        let line = self.value[start..].split('\n').next().unwrap_or_default();
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        start
+            + line
+                .grapheme_indices(true)
+                .nth(column)
+                .map_or(line.len(), |(i, _)| i)
+    }
+
+    /// Start of the word before the cursor, skipping any whitespace in between.
+    fn word_start(&self) -> usize {
+        self.value[..self.cursor]
+            .split_word_bound_indices()
+            .rev()
+            .find(|(_, word)| !word.trim().is_empty())
+            .map_or(0, |(i, _)| i)
+    }
+
+    /// End of the word after the cursor, skipping any whitespace in between.
+    fn word_end(&self) -> usize {
+        self.value[self.cursor..]
+            .split_word_bound_indices()
+            .find(|(_, word)| !word.trim().is_empty())
+            .map_or(self.value.len(), |(i, word)| self.cursor + i + word.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `value` with the cursor placed where `|` is.
+    fn editor(value: &str) -> LineEditor {
+        LineEditor {
+            value: value.replace('|', ""),
+            cursor: value.find('|').unwrap(),
+        }
+    }
+
+    /// The text with `|` marking the cursor.
+    fn shown(editor: &LineEditor) -> String {
+        let (before, under, after) = editor.split();
+        format!("{before}|{under}{after}")
+    }
+
+    #[test]
+    fn inserts_between_graphemes() {
+        let mut e = editor("cafe\u{301}|");
+        e.move_left();
+        e.insert('!');
+        assert_eq!(shown(&e), "caf!|e\u{301}");
+        e.insert_str("日本");
+        assert_eq!(shown(&e), "caf!日本|e\u{301}");
+    }
+
+    #[test]
+    fn deletes_whole_graphemes() {
+        let mut e = editor("ok👍🏽|");
+        e.backspace();
+        assert_eq!(shown(&e), "ok|");
+        let mut e = editor("a|e\u{301}👨\u{200d}👩\u{200d}👧語");
+        e.delete();
+        assert_eq!(shown(&e), "a|👨\u{200d}👩\u{200d}👧語");
+        e.delete();
+        assert_eq!(shown(&e), "a|語");
+        e.delete();
+        e.delete();
+        assert_eq!(shown(&e), "a|");
+        e.move_home();
+        e.backspace();
+        assert_eq!(shown(&e), "|a");
+    }
+
+    #[test]
+    fn moves_a_grapheme_at_a_time() {
+        let mut e = editor("|a👨\u{200d}👩\u{200d}👧e\u{301}日");
+        let mut stops = Vec::new();
+        for _ in 0..5 {
+            e.move_right();
+            stops.push(shown(&e));
+        }
+        assert_eq!(
+            stops,
+            [
+                "a|👨\u{200d}👩\u{200d}👧e\u{301}日",
+                "a👨\u{200d}👩\u{200d}👧|e\u{301}日",
+                "a👨\u{200d}👩\u{200d}👧e\u{301}|日",
+                "a👨\u{200d}👩\u{200d}👧e\u{301}日|",
+                "a👨\u{200d}👩\u{200d}👧e\u{301}日|",
+            ]
+        );
+        e.move_left();
+        e.move_left();
+        assert_eq!(shown(&e), "a👨\u{200d}👩\u{200d}👧|e\u{301}日");
+        e.move_end();
+        e.move_home();
+        e.move_left();
+        assert_eq!(shown(&e), "|a👨\u{200d}👩\u{200d}👧e\u{301}日");
+    }
+
+    #[test]
+    fn kills_words() {
+        let mut e = editor("naïve cafe\u{301}  |");
+        e.kill_word();
+        assert_eq!(shown(&e), "naïve |");
+        let mut e = editor("東京 👍🏽|x");
+        e.kill_word();
+        assert_eq!(shown(&e), "東京 |x");
+        e.kill_line();
+        assert_eq!(shown(&e), "|x");
+    }
+
+    #[test]
+    fn moves_between_words() {
+        let mut e = editor("|one e\u{301}te");
+        e.word_right();
+        assert_eq!(shown(&e), "one| e\u{301}te");
+        e.word_right();
+        assert_eq!(shown(&e), "one e\u{301}te|");
+        e.word_left();
+        assert_eq!(shown(&e), "one |e\u{301}te");
+    }
+
+    #[test]
+    fn moves_between_lines_by_grapheme_column() {
+        let mut e = editor("ab\r\n日本語\ne\u{301}x|");
+        e.line_up();
+        assert_eq!(shown(&e), "ab\r\n日本|語\ne\u{301}x");
+        e.line_up();
+        assert_eq!(shown(&e), "ab|\r\n日本語\ne\u{301}x");
+        e.line_up();
+        assert_eq!(shown(&e), "|ab\r\n日本語\ne\u{301}x");
+        e.move_right();
+        e.line_down();
+        assert_eq!(shown(&e), "ab\r\n日|本語\ne\u{301}x");
+        e.line_down();
+        assert_eq!(shown(&e), "ab\r\n日本語\ne\u{301}|x");
+        e.line_down();
+        assert_eq!(shown(&e), "ab\r\n日本語\ne\u{301}x|");
+    }
+}
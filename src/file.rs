@@ -0,0 +1,65 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufWriter},
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Replaces the file at `path` with what `write` produces, without ever leaving it half written.
+///
+/// The contents go to a temporary file in the same directory, which is
+/// flushed to disk and then renamed over the original. If anything fails
+/// the original file is left untouched and the temporary file is removed.
+pub fn write_atomic(
+    path: &Path,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+    let directory = path.parent().unwrap_or(Path::new("."));
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = directory.join(format!(".{name}.{}.tmp", process::id()));
+
+    let result = (|| {
+        let file = File::create(&temp_path)?;
+        if let Ok(metadata) = fs::metadata(path) {
+            file.set_permissions(metadata.permissions())?;
+        }
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.into_inner()?.sync_all()?;
+        fs::rename(&temp_path, path)
+    })();
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+
+    // Make the rename itself durable. Not every platform can open a
+    // directory for syncing, and the data is already safe by now.
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+/// Path of the backup kept next to `path`, e.g. `data.csv~`.
+pub fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push("~");
+    PathBuf::from(backup)
+}
+
+/// Path of the undo history kept next to `path`, e.g. `.data.csv.history.json`.
+pub fn history_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.history.json"))
+}
+
+/// Hashes `contents` with 64-bit FNV-1a.
+///
+/// Unlike the standard library hashers the result is stable across
+/// releases, so it can be stored on disk.
+pub fn content_hash(contents: &[u8]) -> u64 {
+    contents.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
@@ -0,0 +1,326 @@
+use std::{
+    ops::{Bound, RangeBounds},
+    str::FromStr,
+};
+
+use regex::Regex;
+
+use crate::types;
+
+/// A test a cell has to pass for its row to be shown.
+#[derive(Debug, Clone)]
+pub enum Predicate {
+    /// The cell is exactly this, ignoring surrounding spaces.
+    Equals(String),
+    /// The cell is anything but this, ignoring surrounding spaces.
+    NotEquals(String),
+    /// The cell contains this, ignoring case.
+    Contains(String),
+    /// The cell matches this pattern.
+    Matches(Regex),
+    /// The cell is a number within these bounds.
+    Range(Bound<f64>, Bound<f64>),
+    /// The cell is blank.
+    Empty,
+    /// The cell is not blank.
+    NotEmpty,
+}
+
+impl Predicate {
+    /// Does `cell` pass?
+    pub fn test(&self, cell: &str) -> bool {
+        let cell = cell.trim();
+        match self {
+            Self::Equals(value) => cell == value,
+            Self::NotEquals(value) => cell != value,
+            Self::Contains(value) => cell.to_lowercase().contains(&value.to_lowercase()),
+            Self::Matches(pattern) => pattern.is_match(cell),
+            Self::Range(min, max) => {
+                types::parse_number(cell).is_some_and(|n| (*min, *max).contains(&n))
+            }
+            Self::Empty => cell.is_empty(),
+            Self::NotEmpty => !cell.is_empty(),
+        }
+    }
+}
+
+/// A predicate on one column, as typed into the filter prompt, before the column is looked up.
+#[derive(Debug, Clone)]
+pub struct Condition {
+    /// Column letters, a number counting from one, or a header.
+    pub col: String,
+    /// What the cells of the column have to pass.
+    pub predicate: Predicate,
+}
+
+/// Conditions a row has to meet every one of to be shown.
+#[derive(Debug, Clone)]
+pub struct Conditions(pub Vec<Condition>);
+
+impl FromStr for Conditions {
+    type Err = String;
+
+    /// Reads conditions joined by `and`, each a column, an operator and a
+    /// value, as in `Status = FAILED and Amount in 10..100`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        split_and(s)
+            .into_iter()
+            .map(parse_condition)
+            .collect::<Result<_, _>>()
+            .and_then(|conditions: Vec<Condition>| match conditions.is_empty() {
+                true => Err("Expected a condition like `Status = FAILED`".to_string()),
+                false => Ok(Self(conditions)),
+            })
+    }
+}
+
+/// `s` split at each ` and ` that is not inside quotes.
+fn split_and(s: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut quoted = false;
+    for (i, ch) in s.char_indices() {
+        match ch {
+            '"' => quoted = !quoted,
+            ' ' if !quoted
+                && s[i..].len() >= 5
+                && s.is_char_boundary(i + 5)
+                && s[i..i + 5].eq_ignore_ascii_case(" and ") =>
+            {
+                parts.push(s[start..i].trim());
+                start = i + 5;
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts.retain(|part| !part.is_empty());
+    parts
+}
+
+/// Reads one condition: a column, quoted if it holds spaces, an operator and a value.
+fn parse_condition(s: &str) -> Result<Condition, String> {
+    let invalid = || format!("Expected a condition like `Status = FAILED`, got `{s}`");
+    let number = |value: &str| types::parse_number(value).ok_or_else(invalid);
+    let (col, rest) = match s.strip_prefix('"') {
+        Some(quoted) => quoted.split_once('"').ok_or_else(invalid)?,
+        None => s.split_at(
+            s.find(|ch: char| ch.is_whitespace() || "=!~<>".contains(ch))
+                .ok_or_else(invalid)?,
+        ),
+    };
+    let rest = rest.trim();
+    let word = |op: &str| {
+        rest.get(..op.len())
+            .filter(|head| head.eq_ignore_ascii_case(op))
+            .map(|_| unquote(&rest[op.len()..]))
+    };
+    let predicate = if let Some(value) = word("=~") {
+        Predicate::Matches(Regex::new(value).map_err(|err| invalid_pattern(value, &err))?)
+    } else if let Some(value) = word("!=") {
+        Predicate::NotEquals(value.to_string())
+    } else if let Some(value) = word(">=") {
+        Predicate::Range(Bound::Included(number(value)?), Bound::Unbounded)
+    } else if let Some(value) = word("<=") {
+        Predicate::Range(Bound::Unbounded, Bound::Included(number(value)?))
+    } else if let Some(value) = word("=") {
+        Predicate::Equals(value.to_string())
+    } else if let Some(value) = word("~") {
+        Predicate::Contains(value.to_string())
+    } else if let Some(value) = word(">") {
+        Predicate::Range(Bound::Excluded(number(value)?), Bound::Unbounded)
+    } else if let Some(value) = word("<") {
+        Predicate::Range(Bound::Unbounded, Bound::Excluded(number(value)?))
+    } else if let Some(value) = word("in ") {
+        let (min, max) = value.split_once("..").ok_or_else(invalid)?;
+        let bound = |n: &str| match n.trim() {
+            "" => Ok(Bound::Unbounded),
+            n => number(n).map(Bound::Included),
+        };
+        Predicate::Range(bound(min)?, bound(max)?)
+    } else {
+        match rest.to_ascii_lowercase().as_str() {
+            "empty" | "is empty" => Predicate::Empty,
+            "not empty" | "is not empty" => Predicate::NotEmpty,
+            _ => return Err(invalid()),
+        }
+    };
+    let col = col.trim();
+    match col.is_empty() {
+        true => Err(invalid()),
+        false => Ok(Condition {
+            col: col.to_string(),
+            predicate,
+        }),
+    }
+}
+
+/// Says what is wrong with `pattern` on one line.
+fn invalid_pattern(pattern: &str, err: &regex::Error) -> String {
+    // Syntax errors point at the problem over several lines, the last saying what it is.
+    let err = err.to_string();
+    let reason = err.lines().last().unwrap_or_default();
+    format!(
+        "Invalid pattern `{pattern}`: {}",
+        reason.trim_start_matches("error: ")
+    )
+}
+
+/// `s` trimmed, without the quotes around it if it has them.
+fn unquote(s: &str) -> &str {
+    let s = s.trim();
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Rows left showing by a filter, which hides the others without touching them.
+///
+/// Rows are picked when the filter is applied. Edits don't hide or reveal
+/// rows until it is applied again, and rows inserted meanwhile are shown.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// The conditions as typed.
+    pub text: String,
+    /// Shown rows in ascending order, the header row among them.
+    pub rows: Vec<usize>,
+    /// Rows at the top shown whatever they hold, one for a header row.
+    pub header_rows: usize,
+}
+
+impl Filter {
+    /// Is `row` shown?
+    pub fn shows(&self, row: usize) -> bool {
+        self.rows.binary_search(&row).is_ok()
+    }
+
+    /// Place among the shown rows of `row`, or of the first shown row after it.
+    pub fn index(&self, row: usize) -> usize {
+        self.rows.partition_point(|shown| *shown < row)
+    }
+
+    /// Keeps the shown rows in step with a row inserted at `index`, showing it too.
+    pub fn inserted(&mut self, index: usize) {
+        for row in &mut self.rows {
+            *row += (*row >= index) as usize;
+        }
+        let place = self.index(index);
+        self.rows.insert(place, index);
+        self.show_header();
+    }
+
+    /// Keeps the shown rows in step with the row at `index` being removed.
+    pub fn deleted(&mut self, index: usize) {
+        self.rows.retain(|row| *row != index);
+        for row in &mut self.rows {
+            *row -= (*row > index) as usize;
+        }
+        self.show_header();
+    }
+
+    /// Keeps the shown rows in step with row `i` becoming what was row `order[i]`.
+    pub fn reordered(&mut self, order: &[usize]) {
+        let shown: Vec<bool> = order.iter().map(|row| self.shows(*row)).collect();
+        self.rows = (0..order.len()).filter(|i| shown[*i]).collect();
+        self.show_header();
+    }
+
+    /// Keeps the header rows shown, whichever rows they are now.
+    fn show_header(&mut self) {
+        for row in 0..self.header_rows {
+            if !self.shows(row) {
+                let place = self.index(row);
+                self.rows.insert(place, row);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Result<Vec<(String, Predicate)>, String> {
+        s.parse::<Conditions>().map(|conditions| {
+            conditions
+                .0
+                .into_iter()
+                .map(|condition| (condition.col, condition.predicate))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn parses_operators() {
+        let conditions = parse("Status = FAILED and B != x and C ~ err and D =~ ^a+$").unwrap();
+        let cols: Vec<&str> = conditions.iter().map(|(col, _)| col.as_str()).collect();
+        assert_eq!(cols, ["Status", "B", "C", "D"]);
+        assert!(matches!(&conditions[0].1, Predicate::Equals(v) if v == "FAILED"));
+        assert!(matches!(&conditions[1].1, Predicate::NotEquals(v) if v == "x"));
+        assert!(matches!(&conditions[2].1, Predicate::Contains(v) if v == "err"));
+        assert!(conditions[3].1.test("aaa"));
+        assert!(!conditions[3].1.test("ab"));
+    }
+
+    #[test]
+    fn parses_quoted_columns_and_values() {
+        let conditions = parse("\"Last Name\" = \"van der Berg\"").unwrap();
+        assert_eq!(conditions[0].0, "Last Name");
+        assert!(conditions[0].1.test(" van der Berg "));
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let conditions = parse("A in 10..100 and B in ..5 and C >= 2.5 and D < 0").unwrap();
+        let tests = |i: usize, cells: &[&str]| -> Vec<bool> {
+            cells
+                .iter()
+                .map(|cell| conditions[i].1.test(cell))
+                .collect()
+        };
+        assert_eq!(
+            tests(0, &["9", "10", "100", "101", "x"]),
+            [false, true, true, false, false]
+        );
+        assert_eq!(tests(1, &["-3", "5", "6"]), [true, true, false]);
+        assert_eq!(tests(2, &["2.5", "2"]), [true, false]);
+        assert_eq!(tests(3, &["-1", "0"]), [true, false]);
+        assert!(parse("A in 1..x").is_err());
+        assert!(parse("A > ten").is_err());
+    }
+
+    #[test]
+    fn parses_empty_and_not_empty() {
+        let conditions = parse("A empty and B is not empty and C is empty").unwrap();
+        assert!(matches!(conditions[0].1, Predicate::Empty));
+        assert!(matches!(conditions[1].1, Predicate::NotEmpty));
+        assert!(matches!(conditions[2].1, Predicate::Empty));
+        assert!(conditions[0].1.test("  "));
+        assert!(!conditions[1].1.test(""));
+    }
+
+    #[test]
+    fn keeps_and_inside_quotes() {
+        let conditions = parse("\"Salt and Pepper\" = \"this and that\" AND B = 1").unwrap();
+        assert_eq!(conditions.len(), 2);
+        assert_eq!(conditions[0].0, "Salt and Pepper");
+        assert!(matches!(&conditions[0].1, Predicate::Equals(v) if v == "this and that"));
+        assert_eq!(conditions[1].0, "B");
+    }
+
+    #[test]
+    fn reports_what_is_wrong() {
+        assert_eq!(
+            parse("A = 1 and nonsense").unwrap_err(),
+            "Expected a condition like `Status = FAILED`, got `nonsense`"
+        );
+        assert_eq!(
+            parse(" ").unwrap_err(),
+            "Expected a condition like `Status = FAILED`"
+        );
+        assert_eq!(
+            parse("A =~ (x").unwrap_err(),
+            "Invalid pattern `(x`: unclosed group"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use std::str::FromStr;
+
+/// Where the go-to prompt moves the cursor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// A cell by row and column, from a reference like `C1200`.
+    Cell(usize, usize),
+    /// A row, from `row 1200` or just `1200`, keeping the column.
+    Row(usize),
+    /// A column, from `col 3`, keeping the row.
+    Col(usize),
+    /// A column by its header, from `col "Total Available"`, keeping the row.
+    /// Unquoted names may also be spreadsheet letters, as in `col C`.
+    Header(String),
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    /// Reads a target typed as a spreadsheet reference, `row <n>` or
+    /// `col <letters, number or header>`. Rows and columns count from one.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let keyword = s.split_whitespace().next().unwrap_or_default();
+        let rest = s[keyword.len()..].trim();
+        match keyword.to_ascii_lowercase().as_str() {
+            "row" => number(rest).map(Self::Row),
+            "col" | "column" => {
+                if let Some(name) = rest.strip_prefix('"').and_then(|r| r.strip_suffix('"')) {
+                    Ok(Self::Header(name.to_string()))
+                } else if rest.bytes().all(|b| b.is_ascii_digit()) {
+                    number(rest).map(Self::Col)
+                } else {
+                    Ok(Self::Header(rest.to_string()))
+                }
+            }
+            _ if s.bytes().all(|b| b.is_ascii_digit()) => number(s).map(Self::Row),
+            _ => {
+                let split = s
+                    .find(|ch: char| !ch.is_ascii_alphabetic())
+                    .unwrap_or(s.len());
+                match (number(&s[split..]), letters(&s[..split])) {
+                    (Ok(row), Some(col)) => Ok(Self::Cell(row, col)),
+                    _ => Err(format!(
+                        "Expected a cell like C12, `row 12` or `col Name`, got `{s}`"
+                    )),
+                }
+            }
+        }
+    }
+}
+
+/// Index of a row or column given counting from one.
+fn number(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n - 1),
+        _ => Err(format!("`{s}` is not a row or column number")),
+    }
+}
+
+/// Index of the column named by spreadsheet letters, `A` for the first and `AA` after `Z`.
+pub fn letters(s: &str) -> Option<usize> {
+    if s.is_empty() || s.len() > 6 || !s.bytes().all(|b| b.is_ascii_alphabetic()) {
+        return None;
+    }
+    let n = s.bytes().fold(0, |n, b| {
+        n * 26 + (b.to_ascii_uppercase() - b'A') as usize + 1
+    });
+    Some(n - 1)
+}
+
+/// Spreadsheet letters naming column `col`, the reverse of [`letters`].
+pub fn col_letters(col: usize) -> String {
+    let mut n = col + 1;
+    let mut name = Vec::new();
+    while n > 0 {
+        name.push(b'A' + ((n - 1) % 26) as u8);
+        n = (n - 1) / 26;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
@@ -1,62 +1,358 @@
-use crate::app::{App, AppResult};
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crate::{
+    app::{App, AppResult},
+    editor::LineEditor,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 
 /// Handles the key events and updates the state of [`App`].
 pub fn handle_key_events(key_event: KeyEvent, app: &mut App) -> AppResult<()> {
+    app.status = None;
+    if app.confirming_quit {
+        match key_event.code {
+            KeyCode::Char('y') | KeyCode::Char('s') => {
+                // Keep an edit refused for not fitting its column.
+                if app.editing {
+                    app.exit_editing();
+                }
+                app.save();
+                if app.dirty {
+                    app.cancel_quit();
+                } else {
+                    app.quit();
+                }
+            }
+            KeyCode::Char('n') => app.quit(),
+            KeyCode::Esc | KeyCode::Char('c') => app.cancel_quit(),
+            _ => {}
+        }
+        return Ok(());
+    }
+    if app.prompt.is_some() {
+        handle_prompt_keys(key_event, app);
+        return Ok(());
+    }
+    if app.reordering.is_some() {
+        handle_reorder_keys(key_event, app);
+        return Ok(());
+    }
+    if app.replacing.is_some() {
+        match key_event.code {
+            KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                app.finish_replace();
+                app.request_quit();
+            }
+            KeyCode::Char('y') | KeyCode::Enter => app.replace_one(),
+            KeyCode::Char('n') => app.skip_replace(),
+            KeyCode::Char('a') => app.replace_rest(),
+            KeyCode::Char('q') | KeyCode::Esc => app.finish_replace(),
+            _ => {}
+        }
+        return Ok(());
+    }
+    if app.read_only && is_edit_key(app, &key_event) {
+        app.refuse_read_only();
+        return Ok(());
+    }
     match app.editing {
         false => {
             match key_event.code {
-                // Exit application on `ESC` or `q`
+                // Leave selection mode, clear the search highlights, or exit application on `ESC` or `q`
+                KeyCode::Esc if app.anchor.is_some() => app.toggle_selection(),
+                KeyCode::Esc if app.search.is_active() => app.clear_search(),
                 KeyCode::Esc | KeyCode::Char('q') => {
-                    app.quit();
+                    app.request_quit();
                 }
                 // Exit application on `Ctrl-C`
+                KeyCode::Char('c') | KeyCode::Char('C')
+                    if key_event.modifiers == KeyModifiers::CONTROL =>
+                {
+                    app.request_quit();
+                }
+                KeyCode::Char('z') | KeyCode::Char('Z')
+                    if key_event.modifiers == KeyModifiers::CONTROL =>
+                {
+                    app.undo();
+                }
+                KeyCode::Char('y') | KeyCode::Char('Y')
+                    if key_event.modifiers == KeyModifiers::CONTROL =>
+                {
+                    app.redo();
+                }
+                KeyCode::Char('s') | KeyCode::Char('S')
+                    if key_event.modifiers == KeyModifiers::CONTROL =>
+                {
+                    app.save();
+                }
+
+                // Selection handlers
+                KeyCode::Right if key_event.modifiers == KeyModifiers::SHIFT => {
+                    app.start_selection();
+                    app.move_right();
+                }
+                KeyCode::Left if key_event.modifiers == KeyModifiers::SHIFT => {
+                    app.start_selection();
+                    app.move_left();
+                }
+                KeyCode::Up if key_event.modifiers == KeyModifiers::SHIFT => {
+                    app.start_selection();
+                    app.move_up();
+                }
+                KeyCode::Down if key_event.modifiers == KeyModifiers::SHIFT => {
+                    app.start_selection();
+                    app.move_down();
+                }
+                KeyCode::Char('v') => app.toggle_selection(),
+
+                // Move handlers
+                KeyCode::Right if key_event.modifiers == KeyModifiers::ALT => app.move_col(true),
+                KeyCode::Left if key_event.modifiers == KeyModifiers::ALT => app.move_col(false),
+                KeyCode::Up if key_event.modifiers == KeyModifiers::ALT => app.move_row(false),
+                KeyCode::Down if key_event.modifiers == KeyModifiers::ALT => app.move_row(true),
+                KeyCode::Char('o') => app.open_reorder(),
+                KeyCode::Char('s') if key_event.modifiers == KeyModifiers::ALT => app.open_sort(),
+                KeyCode::Char('s') => app.sort_current(false),
+                KeyCode::Char('S') => app.sort_current(true),
+                KeyCode::Char('f') => app.open_filter(),
+                KeyCode::Char('F') => app.clear_filter(),
+
+                KeyCode::Char('y') if app.anchor.is_some() => app.copy_selection(),
+                KeyCode::Char('x') if app.anchor.is_some() => app.cut_selection(),
+                KeyCode::Char('d') | KeyCode::Delete if app.anchor.is_some() => {
+                    app.clear_selection()
+                }
+                KeyCode::Char('p') => app.paste_register(),
 
                 // Counter handlers
                 KeyCode::Right => app.move_right(),
                 KeyCode::Left => app.move_left(),
                 KeyCode::Up => app.move_up(),
                 KeyCode::Down => app.move_down(),
+                KeyCode::PageUp => app.page_up(),
+                KeyCode::PageDown => app.page_down(),
+                KeyCode::Home if key_event.modifiers == KeyModifiers::CONTROL => {
+                    app.move_first_cell()
+                }
+                KeyCode::End if key_event.modifiers == KeyModifiers::CONTROL => {
+                    app.move_last_cell()
+                }
+                KeyCode::Home => app.move_row_start(),
+                KeyCode::End => app.move_row_end(),
                 KeyCode::Enter => app.enter_editing(),
-                KeyCode::Char('y') => app.add_row(),
-                KeyCode::Char('n') => app.remove_row(),
+                KeyCode::Char('e') => app.enter_popup_editing(),
+                KeyCode::Char('y') => app.insert_row(true),
+                KeyCode::Char('Y') => app.insert_row(false),
+                KeyCode::Char('b') => app.delete_row(),
+                KeyCode::Char('/') => app.open_search(),
+                KeyCode::Char('n') => app.next_match(),
+                KeyCode::Char('N') => app.prev_match(),
+                KeyCode::Char('r') => app.open_replace(),
+                KeyCode::Char(':') => app.open_goto(),
                 KeyCode::Char('h') => app.toggle_header_row(),
-                KeyCode::Char('u') => app.add_col(),
-                KeyCode::Char('m') => app.remove_col(),
+                KeyCode::Char('u') => app.insert_col(true),
+                KeyCode::Char('U') => app.insert_col(false),
+                KeyCode::Char('m') => app.delete_col(),
                 KeyCode::Char('j') => app.toggle_label_col(),
+                KeyCode::Char('[') => app.freeze_fewer_cols(),
+                KeyCode::Char(']') => app.freeze_more_cols(),
                 KeyCode::Char('k') => app.toggle_graph_mode(),
+                KeyCode::Char('c') | KeyCode::Char('C') => app.toggle_controls(),
                 // Other handlers you could add here.
-                KeyCode::Char('c') | KeyCode::Char('C') => {
-                    if key_event.modifiers == KeyModifiers::CONTROL {
-                        app.quit();
-                    } else {
-                        app.toggle_controls();
-                    }
-                }
-                KeyCode::Char('z') | KeyCode::Char('Z') => {
-                    if key_event.modifiers == KeyModifiers::CONTROL {
-                        app.undo();
-                    }
-                }
                 _ => {}
             }
         }
-        true => {
-            match key_event.code {
-                KeyCode::Char(j) => {
-                    if key_event.modifiers == KeyModifiers::CONTROL && j == 'c' {
-                        app.quit();
-                    }
-                    app.edit(j);
+        true => match key_event.code {
+            KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+                app.exit_editing();
+                app.request_quit();
+            }
+            KeyCode::Char('d') if key_event.modifiers == KeyModifiers::CONTROL => {
+                app.exit_editing()
+            }
+            KeyCode::Enter if app.multiline => app.editor.insert('\n'),
+            KeyCode::Up if app.multiline => app.editor.line_up(),
+            KeyCode::Down if app.multiline => app.editor.line_down(),
+            KeyCode::Enter => app.exit_editing(),
+            KeyCode::Esc => app.cancel_editing(),
+            _ => {
+                edit_line(&mut app.editor, &key_event);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Handles the keys typed into the prompt in the path bar.
+fn handle_prompt_keys(key_event: KeyEvent, app: &mut App) {
+    let searches = app
+        .prompt
+        .as_ref()
+        .is_some_and(|prompt| prompt.kind.searches());
+    match key_event.code {
+        KeyCode::Enter => app.submit_prompt(),
+        KeyCode::Esc => app.cancel_prompt(),
+        KeyCode::Char('c') if key_event.modifiers == KeyModifiers::ALT && searches => {
+            app.toggle_search_case()
+        }
+        KeyCode::Char('r') if key_event.modifiers == KeyModifiers::ALT && searches => {
+            app.toggle_search_regex()
+        }
+        KeyCode::Char('k') if key_event.modifiers == KeyModifiers::ALT && searches => {
+            app.toggle_search_scope()
+        }
+        KeyCode::Char('a') if key_event.modifiers == KeyModifiers::ALT => app.replace_all(),
+        _ => {
+            if let Some(prompt) = &mut app.prompt {
+                if edit_line(&mut prompt.editor, &key_event) {
+                    app.prompt_changed();
                 }
-                KeyCode::Backspace => app.backspace(),
-                KeyCode::Enter => app.exit_editing(),
+            }
+        }
+    }
+}
 
-                // Other handlers you could add here.
-                _ => {}
+/// Handles the keys pressed in the column-reorder dialog.
+fn handle_reorder_keys(key_event: KeyEvent, app: &mut App) {
+    let Some(reorder) = &mut app.reordering else {
+        return;
+    };
+    let alt = key_event.modifiers == KeyModifiers::ALT;
+    match key_event.code {
+        KeyCode::Enter => app.finish_reorder(),
+        KeyCode::Esc => app.cancel_reorder(),
+        KeyCode::Char('c') if key_event.modifiers == KeyModifiers::CONTROL => {
+            app.cancel_reorder();
+            app.request_quit();
+        }
+        KeyCode::Char(' ') => reorder.grabbed = !reorder.grabbed,
+        KeyCode::Up | KeyCode::Down if alt => {
+            reorder.grabbed = true;
+            match key_event.code {
+                KeyCode::Up => reorder.up(),
+                _ => reorder.down(),
+            }
+            reorder.grabbed = false;
+        }
+        KeyCode::Up => reorder.up(),
+        KeyCode::Down => reorder.down(),
+        KeyCode::Home => reorder.move_to(0),
+        KeyCode::End => reorder.move_to(usize::MAX),
+        _ => {}
+    }
+}
+
+/// Applies the keys shared by every line being typed to `editor`, returning
+/// whether `key_event` was one of them.
+fn edit_line(editor: &mut LineEditor, key_event: &KeyEvent) -> bool {
+    let control = key_event.modifiers == KeyModifiers::CONTROL;
+    match key_event.code {
+        KeyCode::Char('w') if control => editor.kill_word(),
+        KeyCode::Char('u') if control => editor.kill_line(),
+        KeyCode::Char('a') if control => editor.move_home(),
+        KeyCode::Char('e') if control => editor.move_end(),
+        KeyCode::Left if control => editor.word_left(),
+        KeyCode::Right if control => editor.word_right(),
+        KeyCode::Char(ch)
+            if !key_event
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            editor.insert(ch)
+        }
+        KeyCode::Backspace => editor.backspace(),
+        KeyCode::Delete => editor.delete(),
+        KeyCode::Left => editor.move_left(),
+        KeyCode::Right => editor.move_right(),
+        KeyCode::Home => editor.move_home(),
+        KeyCode::End => editor.move_end(),
+        _ => return false,
+    }
+    true
+}
+
+/// Handles the mouse events and updates the state of [`App`].
+pub fn handle_mouse_events(mouse_event: MouseEvent, app: &mut App) -> AppResult<()> {
+    if app.confirming_quit || app.prompt.is_some() || app.replacing.is_some() {
+        return Ok(());
+    }
+    if let Some(reorder) = &mut app.reordering {
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => app.grab_col(mouse_event.row),
+            MouseEventKind::Drag(MouseButton::Left) => app.drag_col(mouse_event.row),
+            MouseEventKind::Up(MouseButton::Left) => app.drop_col(),
+            MouseEventKind::ScrollDown => reorder.down(),
+            MouseEventKind::ScrollUp => reorder.up(),
+            _ => {}
+        }
+        return Ok(());
+    }
+    let shift = mouse_event.modifiers.contains(KeyModifiers::SHIFT);
+    match mouse_event.kind {
+        MouseEventKind::Down(MouseButton::Left) => {
+            app.status = None;
+            if app.editing {
+                app.exit_editing();
+            }
+            // A value refused by its column keeps the cell open.
+            if !app.editing {
+                app.click(mouse_event.column, mouse_event.row);
             }
         }
+        MouseEventKind::Drag(MouseButton::Left) => app.drag(mouse_event.column),
+        MouseEventKind::Up(MouseButton::Left) => app.release(),
+        // Scrolling would move the cursor off the cell being edited.
+        _ if app.editing => {}
+        MouseEventKind::ScrollDown if shift => app.scroll_cols(true),
+        MouseEventKind::ScrollUp if shift => app.scroll_cols(false),
+        MouseEventKind::ScrollRight => app.scroll_cols(true),
+        MouseEventKind::ScrollLeft => app.scroll_cols(false),
+        MouseEventKind::ScrollDown => app.scroll_rows(true),
+        MouseEventKind::ScrollUp => app.scroll_rows(false),
+        _ => {}
     }
+    Ok(())
+}
 
+/// Handles text pasted into the terminal and updates the state of [`App`].
+///
+/// While editing the text goes into the cell, otherwise it is spread over
+/// the grid from the cursor.
+pub fn handle_paste_events(text: &str, app: &mut App) -> AppResult<()> {
+    app.status = None;
+    if app.confirming_quit || app.replacing.is_some() || app.reordering.is_some() {
+        return Ok(());
+    }
+    if let Some(prompt) = &mut app.prompt {
+        prompt.editor.insert_str(&text.replace(['\r', '\n'], " "));
+        app.prompt_changed();
+        return Ok(());
+    }
+    match app.editing {
+        true => app.insert_text(text),
+        false if app.read_only => app.refuse_read_only(),
+        false => app.paste(text),
+    }
     Ok(())
 }
+
+/// Whether `key_event` changes the file when pressed over the grid.
+fn is_edit_key(app: &App, key_event: &KeyEvent) -> bool {
+    match key_event.code {
+        KeyCode::Char('z' | 'Z' | 'y' | 'Y' | 's' | 'S')
+            if key_event.modifiers == KeyModifiers::CONTROL =>
+        {
+            true
+        }
+        // Copies the selection rather than adding a row while selecting.
+        KeyCode::Char('y') => app.anchor.is_none(),
+        KeyCode::Enter
+        | KeyCode::Delete
+        | KeyCode::Char(
+            'Y' | 'b' | 'u' | 'U' | 'm' | 'e' | 'r' | 'x' | 'd' | 'p' | 'o' | 's' | 'S',
+        ) => true,
+        KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down => {
+            key_event.modifiers == KeyModifiers::ALT
+        }
+        _ => false,
+    }
+}
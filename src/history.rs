@@ -0,0 +1,337 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{app::AppResult, file};
+
+/// A reversible change to the value matrix.
+///
+/// Every operation carries enough of the old state to build its
+/// [`inverse`](Operation::inverse), so undoing never needs a snapshot of
+/// the whole matrix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operation {
+    /// The cell at `row`, `col` changed from `old` to `new`.
+    EditCell {
+        row: usize,
+        col: usize,
+        old: String,
+        new: String,
+    },
+    /// `cells` were inserted as a new row at `index`.
+    InsertRow { index: usize, cells: Vec<String> },
+    /// The row at `index`, holding `cells`, was removed.
+    DeleteRow { index: usize, cells: Vec<String> },
+    /// `cells` were inserted top to bottom as a new column at `index`.
+    InsertCol { index: usize, cells: Vec<String> },
+    /// The column at `index`, holding `cells`, was removed.
+    DeleteCol { index: usize, cells: Vec<String> },
+    /// Rows were rearranged so that row `i` is now what was row `order[i]`.
+    ReorderRows { order: Vec<usize> },
+    /// Columns were rearranged so that column `i` is now what was column `order[i]`.
+    ReorderCols { order: Vec<usize> },
+    /// Operations applied in order as a single step, such as a paste.
+    Group(Vec<Operation>),
+}
+
+impl Operation {
+    /// Builds the operation that reverts this one.
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::EditCell { row, col, old, new } => Self::EditCell {
+                row: *row,
+                col: *col,
+                old: new.clone(),
+                new: old.clone(),
+            },
+            Self::InsertRow { index, cells } => Self::DeleteRow {
+                index: *index,
+                cells: cells.clone(),
+            },
+            Self::DeleteRow { index, cells } => Self::InsertRow {
+                index: *index,
+                cells: cells.clone(),
+            },
+            Self::InsertCol { index, cells } => Self::DeleteCol {
+                index: *index,
+                cells: cells.clone(),
+            },
+            Self::DeleteCol { index, cells } => Self::InsertCol {
+                index: *index,
+                cells: cells.clone(),
+            },
+            Self::ReorderRows { order } => Self::ReorderRows {
+                order: invert(order),
+            },
+            Self::ReorderCols { order } => Self::ReorderCols {
+                order: invert(order),
+            },
+            Self::Group(operations) => {
+                Self::Group(operations.iter().rev().map(Self::inverse).collect())
+            }
+        }
+    }
+}
+
+/// The order that puts back what `order` rearranged.
+fn invert(order: &[usize]) -> Vec<usize> {
+    let mut inverse = vec![0; order.len()];
+    for (i, j) in order.iter().enumerate() {
+        inverse[*j] = i;
+    }
+    inverse
+}
+
+/// Undo and redo stacks of [`Operation`]s.
+#[derive(Debug, Default)]
+pub struct History {
+    /// Applied operations, oldest first.
+    done: Vec<Operation>,
+    /// Undone operations, most recently undone last.
+    undone: Vec<Operation>,
+    /// Operations collected since [`History::begin_group`].
+    group: Option<Vec<Operation>>,
+}
+
+impl History {
+    /// Records an applied operation, discarding anything that could be redone.
+    pub fn record(&mut self, operation: Operation) {
+        match &mut self.group {
+            Some(group) => group.push(operation),
+            None => self.done.push(operation),
+        }
+        self.undone.clear();
+    }
+
+    /// Starts collecting operations into one undo step.
+    pub fn begin_group(&mut self) {
+        self.group.get_or_insert_with(Vec::new);
+    }
+
+    /// Are operations being collected into one undo step?
+    pub fn is_grouping(&self) -> bool {
+        self.group.is_some()
+    }
+
+    /// Records the operations collected since [`History::begin_group`] as one step.
+    pub fn end_group(&mut self) {
+        if let Some(group) = self.group.take() {
+            match group.len() {
+                0 => {}
+                1 => self.done.extend(group),
+                _ => self.done.push(Operation::Group(group)),
+            }
+        }
+    }
+
+    /// Moves the last operation to the redo stack and returns what reverts it.
+    pub fn undo(&mut self) -> Option<Operation> {
+        let operation = self.done.pop()?;
+        let inverse = operation.inverse();
+        self.undone.push(operation);
+        Some(inverse)
+    }
+
+    /// Moves the last undone operation back and returns it to be applied again.
+    pub fn redo(&mut self) -> Option<Operation> {
+        let operation = self.undone.pop()?;
+        self.done.push(operation.clone());
+        Some(operation)
+    }
+
+    /// Number of operations currently applied.
+    pub fn position(&self) -> usize {
+        self.done.len()
+    }
+
+    /// Number of operations that are applied or could be redone.
+    pub fn total(&self) -> usize {
+        self.done.len() + self.undone.len()
+    }
+
+    /// Writes the history to the sidecar of `path`, tied to the saved `contents`.
+    pub fn store(&self, path: &Path, contents: &[u8]) -> AppResult<()> {
+        let sidecar = Sidecar {
+            path: path.to_string_lossy().to_string(),
+            hash: file::content_hash(contents),
+            done: self.done.clone(),
+            undone: self.undone.clone(),
+        };
+        let json = serde_json::to_vec(&sidecar)?;
+        file::write_atomic(&file::history_path(path), |out| out.write_all(&json))?;
+        Ok(())
+    }
+
+    /// Reads the history stored for `path` by [`History::store`].
+    ///
+    /// Returns `None` when nothing was stored. A sidecar that belongs to
+    /// another path, can't be read, or was written for different contents
+    /// than `contents` is removed and reported as an error, since replaying
+    /// it against the file would corrupt it.
+    pub fn restore(path: &Path, contents: &[u8]) -> AppResult<Option<Self>> {
+        let sidecar_path = file::history_path(path);
+        let json = match fs::read(&sidecar_path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+        let sidecar = match serde_json::from_slice::<Sidecar>(&json) {
+            Ok(sidecar)
+                if sidecar.path == path.to_string_lossy()
+                    && sidecar.hash == file::content_hash(contents) =>
+            {
+                sidecar
+            }
+            Ok(_) => {
+                let _ = fs::remove_file(&sidecar_path);
+                return Err("file changed since the undo history was saved".into());
+            }
+            Err(err) => {
+                let _ = fs::remove_file(&sidecar_path);
+                return Err(err.into());
+            }
+        };
+        Ok(Some(Self {
+            done: sidecar.done,
+            undone: sidecar.undone,
+            group: None,
+        }))
+    }
+}
+
+/// Undo history as stored next to a file between sessions.
+#[derive(Serialize, Deserialize)]
+struct Sidecar {
+    /// Absolute path of the file the history belongs to.
+    path: String,
+    /// [`file::content_hash`] of the file when the history was stored.
+    hash: u64,
+    /// Applied operations, oldest first.
+    done: Vec<Operation>,
+    /// Undone operations, most recently undone last.
+    undone: Vec<Operation>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(row: usize, old: &str, new: &str) -> Operation {
+        Operation::EditCell {
+            row,
+            col: 0,
+            old: old.to_string(),
+            new: new.to_string(),
+        }
+    }
+
+    fn cells(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|cell| cell.to_string()).collect()
+    }
+
+    #[test]
+    fn inverts_orders() {
+        let order = [2, 0, 3, 1];
+        let inverse = invert(&order);
+        assert_eq!(inverse, [1, 3, 0, 2]);
+        let rows = ["a", "b", "c", "d"];
+        let moved: Vec<&str> = order.iter().map(|i| rows[*i]).collect();
+        let back: Vec<&str> = inverse.iter().map(|i| moved[*i]).collect();
+        assert_eq!(back, rows);
+    }
+
+    #[test]
+    fn inverse_swaps_each_operation() {
+        assert_eq!(edit(1, "a", "b").inverse(), edit(1, "b", "a"));
+        let insert = Operation::InsertRow {
+            index: 2,
+            cells: cells(&["x", "y"]),
+        };
+        let delete = Operation::DeleteRow {
+            index: 2,
+            cells: cells(&["x", "y"]),
+        };
+        assert_eq!(insert.inverse(), delete);
+        assert_eq!(delete.inverse(), insert);
+        let insert = Operation::InsertCol {
+            index: 0,
+            cells: cells(&["x"]),
+        };
+        let delete = Operation::DeleteCol {
+            index: 0,
+            cells: cells(&["x"]),
+        };
+        assert_eq!(insert.inverse(), delete);
+        assert_eq!(delete.inverse(), insert);
+        assert_eq!(
+            Operation::ReorderRows {
+                order: vec![1, 2, 0]
+            }
+            .inverse(),
+            Operation::ReorderRows {
+                order: vec![2, 0, 1]
+            }
+        );
+        assert_eq!(
+            Operation::ReorderCols { order: vec![1, 0] }.inverse(),
+            Operation::ReorderCols { order: vec![1, 0] }
+        );
+    }
+
+    #[test]
+    fn inverse_of_a_group_runs_backwards() {
+        let group = Operation::Group(vec![edit(0, "a", "b"), edit(0, "b", "c")]);
+        assert_eq!(
+            group.inverse(),
+            Operation::Group(vec![edit(0, "c", "b"), edit(0, "b", "a")])
+        );
+        assert_eq!(group.inverse().inverse(), group);
+    }
+
+    #[test]
+    fn groups_collect_into_one_step() {
+        let mut history = History::default();
+        history.begin_group();
+        history.end_group();
+        assert_eq!(history.total(), 0);
+
+        history.begin_group();
+        history.record(edit(0, "a", "b"));
+        history.end_group();
+        assert_eq!(history.undo(), Some(edit(0, "b", "a")));
+        history.redo();
+
+        history.begin_group();
+        assert!(history.is_grouping());
+        history.record(edit(1, "a", "b"));
+        history.record(edit(2, "a", "b"));
+        history.end_group();
+        assert!(!history.is_grouping());
+        assert_eq!(history.position(), 2);
+        assert_eq!(
+            history.undo(),
+            Some(Operation::Group(vec![edit(2, "b", "a"), edit(1, "b", "a")]))
+        );
+        assert_eq!(history.undo(), Some(edit(0, "b", "a")));
+        assert_eq!(history.undo(), None);
+    }
+
+    #[test]
+    fn recording_clears_what_could_be_redone() {
+        let mut history = History::default();
+        history.record(edit(0, "a", "b"));
+        history.record(edit(0, "b", "c"));
+        history.undo();
+        assert_eq!(history.total(), 2);
+        assert_eq!(history.redo(), Some(edit(0, "b", "c")));
+        history.undo();
+        history.record(edit(0, "b", "d"));
+        assert_eq!(history.total(), 2);
+        assert_eq!(history.redo(), None);
+        assert_eq!(history.undo(), Some(edit(0, "d", "b")));
+    }
+}
@@ -5,29 +5,43 @@ use std::{
 
 use crate::{
     app::{App, AppResult},
+    cli::Args,
     event::{Event, EventHandler},
-    handler::handle_key_events,
+    handler::{handle_key_events, handle_mouse_events, handle_paste_events},
     theme::Theme,
     tui::Tui,
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
 
 pub mod app;
+pub mod cli;
+pub mod csv;
+pub mod editor;
 pub mod event;
+pub mod file;
+pub mod filter;
+pub mod goto;
 pub mod handler;
+pub mod history;
+pub mod prompt;
+pub mod reorder;
+pub mod search;
+pub mod sort;
+pub mod stream;
 pub mod theme;
 pub mod tui;
+pub mod types;
 pub mod ui;
 use std::env;
 
 #[tokio::main]
 async fn main() -> AppResult<()> {
-    let args: Vec<String> = env::args().collect();
+    let args = Args::parse()?;
     // Create an application.
-    let mut app = App::new(args[1].clone());
+    let mut app = App::new(&args);
     let source_directory = env::current_exe().unwrap().parent().unwrap().parent().unwrap().parent().unwrap().to_string_lossy().to_string();
     let theme_string = fs::read_to_string(source_directory+"/theme.json");
-    let theme: Theme = match serde_json::from_str(&theme_string.unwrap().as_str()) {
+    let theme: Theme = match serde_json::from_str(theme_string.unwrap().as_str()) {
         Ok(them) => them,
         Err(_) => serde_json::from_str(
             "
@@ -62,7 +76,8 @@ async fn main() -> AppResult<()> {
         match tui.events.next().await? {
             Event::Tick => app.tick(),
             Event::Key(key_event) => handle_key_events(key_event, &mut app)?,
-            Event::Mouse(_) => {}
+            Event::Mouse(mouse_event) => handle_mouse_events(mouse_event, &mut app)?,
+            Event::Paste(text) => handle_paste_events(&text, &mut app)?,
             Event::Resize(_, _) => {}
         }
     }
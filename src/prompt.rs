@@ -0,0 +1,69 @@
+use std::ops::Range;
+
+use crate::editor::LineEditor;
+
+/// What a [`Prompt`] asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    /// Text to search the cells for, searched as it is typed.
+    Search,
+    /// Text to replace, searched as it is typed.
+    Find,
+    /// What to replace the found text with.
+    Replace,
+    /// A cell, row or column to move to.
+    Goto,
+    /// Columns to sort the rows by.
+    Sort,
+    /// Conditions rows have to meet to be shown.
+    Filter,
+}
+
+impl PromptKind {
+    /// Shown in front of what is typed.
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Search => "/",
+            Self::Find => "Find: ",
+            Self::Replace => "Replace with: ",
+            Self::Goto => ":",
+            Self::Sort => "Sort by: ",
+            Self::Filter => "Show rows where: ",
+        }
+    }
+
+    /// Does the prompt take the search options?
+    pub fn searches(self) -> bool {
+        !matches!(self, Self::Goto | Self::Sort | Self::Filter)
+    }
+}
+
+/// A line typed into the path bar.
+#[derive(Debug, Clone)]
+pub struct Prompt {
+    /// What the line is for.
+    pub kind: PromptKind,
+    /// The line being typed.
+    pub editor: LineEditor,
+    /// Cell the cursor was on when the prompt opened, returned to when it is cancelled.
+    pub origin: (usize, usize),
+    /// Rows and columns selected when the prompt opened.
+    pub selection: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl Prompt {
+    /// Opens a prompt for `kind`, starting from `value`.
+    pub fn new(
+        kind: PromptKind,
+        value: String,
+        origin: (usize, usize),
+        selection: Option<(Range<usize>, Range<usize>)>,
+    ) -> Self {
+        Self {
+            kind,
+            editor: LineEditor::new(value),
+            origin,
+            selection,
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use ratatui::layout::Rect;
+
+/// The column-reorder dialog: every column listed in its new order.
+#[derive(Debug, Clone, Default)]
+pub struct ColumnOrder {
+    /// Column shown at each place in the list, as it was numbered when the dialog opened.
+    pub order: Vec<usize>,
+    /// Place in the list under the cursor.
+    pub selected: usize,
+    /// Does moving the cursor carry the selected column along?
+    pub grabbed: bool,
+    /// Where the list was drawn, as of the last render.
+    pub area: Rect,
+    /// Place in the list drawn first, as of the last render.
+    pub top: usize,
+}
+
+impl ColumnOrder {
+    /// Lists `cols` columns in their current order with `selected` under the cursor.
+    pub fn new(cols: usize, selected: usize) -> Self {
+        Self {
+            order: (0..cols).collect(),
+            selected,
+            ..Self::default()
+        }
+    }
+
+    pub fn up(&mut self) {
+        self.move_to(self.selected.saturating_sub(1));
+    }
+    pub fn down(&mut self) {
+        self.move_to(self.selected + 1);
+    }
+
+    /// Moves the cursor to `place`, carrying the selected column there if it is grabbed.
+    pub fn move_to(&mut self, place: usize) {
+        let place = place.min(self.order.len() - 1);
+        if self.grabbed {
+            let col = self.order.remove(self.selected);
+            self.order.insert(place, col);
+        }
+        self.selected = place;
+    }
+
+    /// Place in the list drawn at screen row `y`, if any.
+    pub fn place_at(&self, y: u16) -> Option<usize> {
+        if y < self.area.y || y >= self.area.bottom() {
+            return None;
+        }
+        let place = self.top + (y - self.area.y) as usize;
+        (place < self.order.len()).then_some(place)
+    }
+
+    /// Has anything been moved?
+    pub fn is_changed(&self) -> bool {
+        self.order.iter().enumerate().any(|(i, j)| i != *j)
+    }
+}
@@ -0,0 +1,167 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    ops::{
+        Bound::{Excluded, Unbounded},
+        Range,
+    },
+};
+
+use regex::{NoExpand, Regex, RegexBuilder};
+
+/// Which cells a [`Search`] looks through.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Scope {
+    /// Every cell.
+    #[default]
+    Sheet,
+    /// The cells of one column.
+    Column(usize),
+    /// The cells of a selected rectangle.
+    Selection {
+        rows: Range<usize>,
+        cols: Range<usize>,
+    },
+}
+
+impl Scope {
+    /// Is the cell at `row`, `col` looked through?
+    pub fn contains(&self, row: usize, col: usize) -> bool {
+        match self {
+            Self::Sheet => true,
+            Self::Column(column) => col == *column,
+            Self::Selection { rows, cols } => rows.contains(&row) && cols.contains(&col),
+        }
+    }
+}
+
+/// A search through the cells and the cells it found.
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    /// Text, or pattern, to look for. Nothing is searched for while empty.
+    pub query: String,
+    /// Match upper and lower case exactly.
+    pub case_sensitive: bool,
+    /// Read the query as a regular expression.
+    pub regex: bool,
+    /// Cells looked through.
+    pub scope: Scope,
+    /// Matching cells by row and column, in reading order.
+    pub matches: BTreeSet<(usize, usize)>,
+    /// Is the query a pattern that does not compile?
+    pub invalid: bool,
+}
+
+impl Search {
+    /// The query as a regular expression, escaped unless [`Search::regex`] is set.
+    pub fn pattern(&self) -> Result<Regex, regex::Error> {
+        let pattern = match self.regex {
+            true => self.query.clone(),
+            false => regex::escape(&self.query),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+    }
+
+    /// Finds the cells of `matrix` that match.
+    pub fn run(&mut self, matrix: &[Vec<String>]) {
+        self.matches.clear();
+        self.invalid = false;
+        if self.query.is_empty() {
+            return;
+        }
+        let Ok(pattern) = self.pattern() else {
+            self.invalid = true;
+            return;
+        };
+        for (i, row) in matrix.iter().enumerate() {
+            for (j, cell) in row.iter().enumerate() {
+                if self.scope.contains(i, j) && pattern.is_match(cell) {
+                    self.matches.insert((i, j));
+                }
+            }
+        }
+    }
+
+    /// Forgets the query and what it found.
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.matches.clear();
+        self.invalid = false;
+    }
+
+    /// Is there a query being searched for?
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty()
+    }
+
+    /// The first match at or after `cell`, wrapping around to the top.
+    pub fn at_or_after(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.matches
+            .range(cell..)
+            .next()
+            .or(self.matches.first())
+            .copied()
+    }
+
+    /// The first match after `cell`, wrapping around to the top.
+    pub fn after(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.matches
+            .range((Excluded(cell), Unbounded))
+            .next()
+            .or(self.matches.first())
+            .copied()
+    }
+
+    /// The last match before `cell`, wrapping around to the bottom.
+    pub fn before(&self, cell: (usize, usize)) -> Option<(usize, usize)> {
+        self.matches
+            .range(..cell)
+            .next_back()
+            .or(self.matches.last())
+            .copied()
+    }
+}
+
+/// A find and replace stepping through the cells a [`Search`] found.
+#[derive(Debug, Clone)]
+pub struct Replace {
+    /// What is replaced.
+    pattern: Regex,
+    /// What each match is replaced with.
+    replacement: String,
+    /// Does the replacement refer to capture groups, as in `$1`?
+    expand: bool,
+    /// Cells still to be replaced or skipped, by row and column.
+    pub pending: VecDeque<(usize, usize)>,
+    /// Number of cells replaced so far.
+    pub replaced: usize,
+}
+
+impl Replace {
+    /// Starts replacing what `search` found with `replacement`, beginning
+    /// at the first match from `from` and wrapping around. Capture groups
+    /// can only be referred to when searching by regex.
+    pub fn new(search: &Search, replacement: String, from: (usize, usize)) -> Option<Self> {
+        let pattern = search.pattern().ok()?;
+        let mut pending: VecDeque<_> = search.matches.iter().copied().collect();
+        let before = pending.iter().take_while(|cell| **cell < from).count();
+        pending.rotate_left(before);
+        Some(Self {
+            pattern,
+            replacement,
+            expand: search.regex,
+            pending,
+            replaced: 0,
+        })
+    }
+
+    /// `cell` with every match replaced.
+    pub fn apply(&self, cell: &str) -> String {
+        match self.expand {
+            true => self.pattern.replace_all(cell, self.replacement.as_str()),
+            false => self.pattern.replace_all(cell, NoExpand(&self.replacement)),
+        }
+        .into_owned()
+    }
+}
@@ -0,0 +1,44 @@
+use std::str::FromStr;
+
+/// A column to sort by, as typed into the sort prompt, before the column is looked up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    /// Column letters, a number counting from one, or a header.
+    pub col: String,
+    /// Sort from the largest down.
+    pub descending: bool,
+}
+
+/// The columns to sort by, most significant first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKeys(pub Vec<SortKey>);
+
+impl FromStr for SortKeys {
+    type Err = String;
+
+    /// Reads keys separated by commas, each a column optionally followed by
+    /// `asc` or `desc`, as in `C desc, "Last Name", 2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut keys = Vec::new();
+        for key in s.split(',').map(str::trim) {
+            let (col, descending) = match key.rsplit_once(char::is_whitespace) {
+                Some((col, order)) if order.eq_ignore_ascii_case("desc") => (col, true),
+                Some((col, order)) if order.eq_ignore_ascii_case("asc") => (col, false),
+                _ => (key, false),
+            };
+            let col = col.trim();
+            let col = col
+                .strip_prefix('"')
+                .and_then(|c| c.strip_suffix('"'))
+                .unwrap_or(col);
+            if col.is_empty() {
+                return Err(format!("Expected columns like `C desc, A`, got `{s}`"));
+            }
+            keys.push(SortKey {
+                col: col.to_string(),
+                descending,
+            });
+        }
+        Ok(Self(keys))
+    }
+}
@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::{self, BufWriter, Cursor, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+    slice,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
+
+use memmap2::Mmap;
+
+use crate::csv;
+
+/// Bytes read at a time while indexing.
+const CHUNK_SIZE: usize = 1 << 20;
+
+/// Rows kept parsed before the cache is dropped.
+const CACHE_ROWS: usize = 4096;
+
+/// Byte offsets of the records of a file, filled in by a background thread.
+#[derive(Debug)]
+pub struct Index {
+    /// Offset at which each record found so far starts.
+    starts: Mutex<Vec<u64>>,
+    /// Bytes of the file scanned so far.
+    scanned: AtomicU64,
+    /// Size of the file.
+    len: u64,
+    /// Has the whole file been scanned?
+    done: AtomicBool,
+}
+
+impl Index {
+    /// Starts indexing `file` on a new thread and returns the shared index.
+    ///
+    /// Quotes are tracked the way [`csv::parse`] reads them: a `"` only
+    /// opens a quoted field at the start of a field, `""` inside one is an
+    /// escaped quote, and any other quote is plain text.
+    fn build(mut file: impl Read + Send + 'static, len: u64, delimiter: char) -> Arc<Self> {
+        let index = Arc::new(Self {
+            starts: Mutex::new(vec![0]),
+            scanned: AtomicU64::new(0),
+            len,
+            done: AtomicBool::new(false),
+        });
+        let shared = index.clone();
+        thread::spawn(move || {
+            let mut buffer = vec![0; CHUNK_SIZE];
+            let mut offset = 0;
+            let mut delimiter_bytes = [0; 4];
+            let delimiter = delimiter.encode_utf8(&mut delimiter_bytes).as_bytes();
+            // The last bytes scanned, to spot a delimiter split across reads.
+            let mut recent = [0; 4];
+            let mut in_quotes = false;
+            // Was the last byte a quote inside a quoted field, which either
+            // closes it or escapes the next one?
+            let mut quote_pending = false;
+            let mut field_start = true;
+            let mut after_cr = false;
+            loop {
+                let read = match file.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => read,
+                };
+                let mut starts = Vec::new();
+                for (i, byte) in buffer[..read].iter().enumerate() {
+                    let position = offset + i as u64;
+                    // A lone `\r` ends a record just like `\n` and `\r\n` do.
+                    if after_cr && *byte != b'\n' {
+                        starts.push(position);
+                    }
+                    after_cr = false;
+                    recent.rotate_left(1);
+                    recent[3] = *byte;
+                    if quote_pending {
+                        quote_pending = false;
+                        if *byte == b'"' {
+                            continue;
+                        }
+                        in_quotes = false;
+                    }
+                    if in_quotes {
+                        quote_pending = *byte == b'"';
+                        continue;
+                    }
+                    match byte {
+                        b'"' if field_start => in_quotes = true,
+                        b'\n' => starts.push(position + 1),
+                        b'\r' => after_cr = true,
+                        _ => {}
+                    }
+                    field_start = matches!(byte, b'\n' | b'\r') || recent.ends_with(delimiter);
+                }
+                offset += read as u64;
+                shared.starts.lock().unwrap().extend(starts);
+                shared.scanned.store(offset, Ordering::Relaxed);
+            }
+            let mut starts = shared.starts.lock().unwrap();
+            if after_cr {
+                starts.push(offset);
+            }
+            // A trailing line break does not start another record.
+            if starts.last() == Some(&offset) {
+                starts.pop();
+            }
+            shared.done.store(true, Ordering::Release);
+        });
+        index
+    }
+
+    /// Number of complete records indexed so far.
+    fn rows(&self) -> usize {
+        let starts = self.starts.lock().unwrap().len();
+        if self.done.load(Ordering::Acquire) {
+            starts
+        } else {
+            // The last start may belong to a record still being scanned.
+            starts.saturating_sub(1)
+        }
+    }
+
+    /// Byte range of the record at `row`.
+    fn range(&self, row: usize) -> Option<(u64, u64)> {
+        let starts = self.starts.lock().unwrap();
+        let start = *starts.get(row)?;
+        let end = match starts.get(row + 1) {
+            Some(end) => *end,
+            None if self.done.load(Ordering::Acquire) => self.len,
+            None => return None,
+        };
+        Some((start, end))
+    }
+}
+
+/// Where a [`Stream`] reads its rows from.
+#[derive(Debug)]
+enum Source {
+    /// An open file, read with a seek per row.
+    File(File),
+    /// A read-only memory map of the file, sliced per row.
+    Map(SharedMap),
+}
+
+/// A memory map the indexing thread can read alongside the [`Stream`].
+#[derive(Debug, Clone)]
+struct SharedMap(Arc<Mmap>);
+
+impl AsRef<[u8]> for SharedMap {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+/// A csv file read a few rows at a time instead of loaded whole.
+///
+/// Rows are parsed on demand from the [`Index`], and edited rows are kept
+/// in memory until the file is written back.
+#[derive(Debug)]
+pub struct Stream {
+    /// Path of the file.
+    path: PathBuf,
+    /// Where rows are read from.
+    source: Source,
+    /// Record offsets.
+    index: Arc<Index>,
+    /// Delimiter of the file.
+    delimiter: char,
+    /// Widest row seen so far.
+    width: usize,
+    /// Recently parsed rows.
+    cache: HashMap<usize, Vec<String>>,
+    /// Edited rows and whether each of their fields was quoted.
+    edits: BTreeMap<usize, (Vec<String>, Vec<bool>)>,
+}
+
+impl Stream {
+    /// Opens `path` and starts indexing it in the background.
+    pub fn open(path: &Path, delimiter: char, width: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // A separate handle, as a cloned one would share the read position.
+        let index = Index::build(File::open(path)?, file.metadata()?.len(), delimiter);
+        Ok(Self {
+            path: path.to_path_buf(),
+            source: Source::File(file),
+            index,
+            delimiter,
+            width,
+            cache: HashMap::new(),
+            edits: BTreeMap::new(),
+        })
+    }
+
+    /// Memory-maps `path` for viewing and starts indexing it in the background.
+    ///
+    /// Rows are sliced straight out of the map, so nothing is copied until
+    /// it is shown. The stream must not be edited or written back.
+    pub fn map(path: &Path, delimiter: char, width: usize) -> io::Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: the map is only ever read. Another process truncating the
+        // file while it is open is outside of what this viewer can guard
+        // against, as with any memory-mapped reader.
+        let map = SharedMap(Arc::new(unsafe { Mmap::map(&file)? }));
+        let index = Index::build(Cursor::new(map.clone()), map.0.len() as u64, delimiter);
+        Ok(Self {
+            path: path.to_path_buf(),
+            source: Source::Map(map),
+            index,
+            delimiter,
+            width,
+            cache: HashMap::new(),
+            edits: BTreeMap::new(),
+        })
+    }
+
+    /// Number of rows indexed so far.
+    pub fn rows(&self) -> usize {
+        self.index.rows()
+    }
+
+    /// Number of columns in the widest row seen so far.
+    pub fn cols(&self) -> usize {
+        self.width
+    }
+
+    /// Indexing progress as bytes scanned out of the file size, or `None` once done.
+    pub fn progress(&self) -> Option<(u64, u64)> {
+        match self.index.done.load(Ordering::Acquire) {
+            true => None,
+            false => Some((self.index.scanned.load(Ordering::Relaxed), self.index.len)),
+        }
+    }
+
+    /// Fields of the row at `row`, padded to [`Stream::cols`].
+    pub fn row(&mut self, row: usize) -> Vec<String> {
+        let mut fields = match self.edits.get(&row) {
+            Some((fields, _)) => fields.clone(),
+            None => match self.cache.get(&row) {
+                Some(fields) => fields.clone(),
+                None => match self.read(row) {
+                    Ok((fields, _)) => {
+                        if self.cache.len() >= CACHE_ROWS {
+                            self.cache.clear();
+                        }
+                        self.cache.insert(row, fields.clone());
+                        fields
+                    }
+                    // Not indexed yet.
+                    Err(_) => Vec::new(),
+                },
+            },
+        };
+        self.width = self.width.max(fields.len());
+        fields.resize(self.width, String::new());
+        fields
+    }
+
+    /// Changes one cell, keeping the edited row in memory.
+    pub fn set(&mut self, row: usize, col: usize, value: String) {
+        if !self.edits.contains_key(&row) {
+            let edit = self.read(row).unwrap_or_default();
+            self.edits.insert(row, edit);
+        }
+        let (fields, quoted) = self.edits.get_mut(&row).unwrap();
+        if fields.len() <= col {
+            fields.resize(col + 1, String::new());
+            quoted.resize(col + 1, false);
+        }
+        fields[col] = value;
+    }
+
+    /// Parses the record at `row` straight from the file.
+    fn read(&mut self, row: usize) -> io::Result<(Vec<String>, Vec<bool>)> {
+        let (start, end) = self.index.range(row).ok_or(io::ErrorKind::UnexpectedEof)?;
+        let document = match &mut self.source {
+            Source::File(file) => {
+                let mut bytes = vec![0; (end - start) as usize];
+                file.seek(SeekFrom::Start(start))?;
+                file.read_exact(&mut bytes)?;
+                csv::parse(&String::from_utf8_lossy(&bytes), self.delimiter)
+            }
+            Source::Map(map) => csv::parse(
+                &String::from_utf8_lossy(&map.0[start as usize..end as usize]),
+                self.delimiter,
+            ),
+        };
+        Ok((
+            document.records.into_iter().next().unwrap_or_default(),
+            document.quoted.into_iter().next().unwrap_or_default(),
+        ))
+    }
+
+    /// Writes the file with the edited rows replaced, copying every other byte as is.
+    pub fn write_to(&mut self, out: &mut BufWriter<File>, format: &csv::Format) -> io::Result<()> {
+        let Source::File(file) = &mut self.source else {
+            return Err(io::Error::other("file is open read-only"));
+        };
+        let mut position = 0;
+        file.seek(SeekFrom::Start(0))?;
+        for (row, (fields, quoted)) in &self.edits {
+            let (start, end) = self.index.range(*row).ok_or(io::ErrorKind::UnexpectedEof)?;
+            io::copy(&mut (&mut *file).take(start - position), out)?;
+            let mut original = vec![0; (end - start) as usize];
+            file.read_exact(&mut original)?;
+            let terminator = original.len()
+                - original
+                    .iter()
+                    .rev()
+                    .take_while(|byte| matches!(byte, b'\r' | b'\n'))
+                    .count()
+                    .min(2);
+            out.write_all(
+                csv::write(slice::from_ref(fields), slice::from_ref(quoted), format)
+                    .trim_end_matches(['\r', '\n'])
+                    .as_bytes(),
+            )?;
+            out.write_all(&original[terminator..])?;
+            position = end;
+        }
+        io::copy(file, out)?;
+        Ok(())
+    }
+
+    /// Opens the file again after it was rewritten, keeping nothing from before.
+    pub fn reopen(&mut self) -> io::Result<()> {
+        *self = Self::open(&self.path, self.delimiter, self.width)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offsets of the records of `input` once it is fully indexed.
+    fn starts(input: &str, delimiter: char) -> Vec<u64> {
+        let index = Index::build(
+            Cursor::new(input.to_string()),
+            input.len() as u64,
+            delimiter,
+        );
+        while !index.done.load(Ordering::Acquire) {
+            thread::yield_now();
+        }
+        let starts = index.starts.lock().unwrap().clone();
+        starts
+    }
+
+    #[test]
+    fn tracks_quotes_like_the_parser() {
+        let input = "a,\"b\nc\",d\ne\"f,g\nh,\"i\"\"\nj\"\nk";
+        assert_eq!(csv::parse(input, ',').records.len(), 4);
+        assert_eq!(starts(input, ','), [0, 10, 16, 26]);
+    }
+
+    #[test]
+    fn opens_quotes_only_after_the_delimiter() {
+        assert_eq!(starts("a;\"b\nc\"\nd", ';'), [0, 8]);
+        assert_eq!(starts("a,\"b\nc\"\nd", ';'), [0, 5, 8]);
+        assert_eq!(starts("a\r\"b\rc\"\r\nd", '\t'), [0, 2, 9]);
+    }
+}
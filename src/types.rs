@@ -0,0 +1,350 @@
+use std::cmp::Ordering;
+
+/// Share of the cells that aren't blank which must fit a type for a column to take it.
+const CONFIDENT: f64 = 0.9;
+
+/// What the cells of a column hold, judged from the cells that aren't blank.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnType {
+    /// Whole numbers, as in `-42`.
+    Integer,
+    /// Numbers, as in `3.5` or `1e-3`.
+    Float,
+    /// `true` and `false`, or `yes` and `no`.
+    Boolean,
+    /// Dates written year first, as in `2024-03-09`.
+    Date,
+    /// Dates with a time of day, as in `2024-03-09T14:30:00`.
+    DateTime,
+    /// Anything else.
+    #[default]
+    Text,
+}
+
+/// The type guessed for a column and how sure the guess is.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TypeGuess {
+    pub kind: ColumnType,
+    /// Share of the cells that aren't blank which fit `kind`, from 0 to 1.
+    pub confidence: f64,
+}
+
+impl ColumnType {
+    /// Types tried in turn, most specific first.
+    const CANDIDATES: [Self; 5] = [
+        Self::Integer,
+        Self::Float,
+        Self::Boolean,
+        Self::Date,
+        Self::DateTime,
+    ];
+
+    /// The type that fits the most of `cells` that aren't blank, as long
+    /// as it fits nearly all of them, or text when none does. Of types that
+    /// fit as many cells, the most specific wins.
+    pub fn infer<'a>(cells: impl IntoIterator<Item = &'a str>) -> TypeGuess {
+        let mut fits = [0usize; Self::CANDIDATES.len()];
+        let mut total = 0;
+        for cell in cells.into_iter().map(str::trim).filter(|c| !c.is_empty()) {
+            total += 1;
+            for (fit, kind) in fits.iter_mut().zip(Self::CANDIDATES) {
+                *fit += kind.fits(cell) as usize;
+            }
+        }
+        Self::CANDIDATES
+            .into_iter()
+            .zip(fits)
+            .map(|(kind, fit)| TypeGuess {
+                kind,
+                confidence: fit as f64 / total.max(1) as f64,
+            })
+            .filter(|guess| total > 0 && guess.confidence >= CONFIDENT)
+            .reduce(|best, guess| match guess.confidence > best.confidence {
+                true => guess,
+                false => best,
+            })
+            .unwrap_or(TypeGuess {
+                kind: Self::Text,
+                confidence: 1.0,
+            })
+    }
+
+    /// Does `cell`, which isn't blank, hold a value of this type?
+    pub fn fits(self, cell: &str) -> bool {
+        let cell = cell.trim();
+        match self {
+            Self::Integer => cell.parse::<i64>().is_ok(),
+            Self::Float => parse_number(cell).is_some(),
+            Self::Boolean => parse_bool(cell).is_some(),
+            Self::Date => parse_date(cell).is_some(),
+            Self::DateTime => parse_datetime(cell).is_some(),
+            Self::Text => true,
+        }
+    }
+
+    /// Are values of this type lined up on the right?
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Self::Integer | Self::Float)
+    }
+
+    /// Short name shown in the header.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Integer => "int",
+            Self::Float => "float",
+            Self::Boolean => "bool",
+            Self::Date => "date",
+            Self::DateTime => "datetime",
+            Self::Text => "text",
+        }
+    }
+
+    /// What a cell of this type holds, for telling the user a value doesn't fit.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Self::Integer => "a whole number",
+            Self::Float => "a number",
+            Self::Boolean => "true or false",
+            Self::Date => "a date like 2024-03-09",
+            Self::DateTime => "a date and time like 2024-03-09 14:30",
+            Self::Text => "text",
+        }
+    }
+
+    /// Orders two cells of a column of this type. Cells that don't fit the
+    /// type come after those that do.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (a.trim(), b.trim());
+        let ordering = match self {
+            Self::Integer | Self::Float => {
+                compare_parsed(parse_number(a), parse_number(b), f64::total_cmp)
+            }
+            Self::Boolean => compare_parsed(parse_bool(a), parse_bool(b), Ord::cmp),
+            Self::Date => compare_parsed(parse_date(a), parse_date(b), Ord::cmp),
+            Self::DateTime => compare_parsed(parse_datetime(a), parse_datetime(b), |a, b| {
+                a.0.cmp(&b.0).then(a.1.total_cmp(&b.1))
+            }),
+            Self::Text => Ordering::Equal,
+        };
+        ordering.then_with(|| natural(a, b))
+    }
+}
+
+/// Orders parsed values, putting values that didn't parse last.
+fn compare_parsed<T>(a: Option<T>, b: Option<T>, cmp: impl Fn(&T, &T) -> Ordering) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => cmp(&a, &b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// A finite number, so that words like `nan` and `inf` stay text.
+pub fn parse_number(s: &str) -> Option<f64> {
+    s.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.to_ascii_lowercase().as_str() {
+        "true" | "yes" => Some(true),
+        "false" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Year, month and day of a date written `YYYY-MM-DD` or `YYYY/MM/DD`.
+fn parse_date(s: &str) -> Option<(u32, u32, u32)> {
+    let separator = if s.contains('-') { '-' } else { '/' };
+    let mut parts = s.split(separator);
+    let year = parts.next().filter(|p| p.len() == 4)?;
+    let month = parts.next().filter(|p| (1..=2).contains(&p.len()))?;
+    let day = parts.next().filter(|p| (1..=2).contains(&p.len()))?;
+    if parts.next().is_some() {
+        return None;
+    }
+    let (year, month, day) = (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    ((1..=12).contains(&month) && (1..=31).contains(&day)).then_some((year, month, day))
+}
+
+/// Date and time of day in seconds of a date followed by `HH:MM` or
+/// `HH:MM:SS`, after a `T` or a space. Seconds may have a fraction and a
+/// trailing `Z` is ignored. A date on its own is taken as midnight.
+fn parse_datetime(s: &str) -> Option<((u32, u32, u32), f64)> {
+    let Some((date, time)) = s.split_once(['T', ' ']) else {
+        return Some((parse_date(s)?, 0.0));
+    };
+    let time = time.trim().strip_suffix('Z').unwrap_or(time.trim());
+    let mut parts = time.split(':');
+    let hours: u32 = parts.next().filter(|p| p.len() == 2)?.parse().ok()?;
+    let minutes: u32 = parts.next().filter(|p| p.len() == 2)?.parse().ok()?;
+    let seconds: f64 = match parts.next() {
+        Some(p) if p.len() >= 2 && p.as_bytes()[..2].iter().all(u8::is_ascii_digit) => {
+            p.parse().ok()?
+        }
+        Some(_) => return None,
+        None => 0.0,
+    };
+    if parts.next().is_some() || hours > 23 || minutes > 59 || !(0.0..61.0).contains(&seconds) {
+        return None;
+    }
+    Some((
+        parse_date(date)?,
+        (hours * 3600 + minutes * 60) as f64 + seconds,
+    ))
+}
+
+/// Orders text the way people expect, with runs of digits compared as
+/// numbers, so `item2` comes before `item10`, and case ignored until it is
+/// the only difference.
+pub fn natural(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+    loop {
+        let ordering = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) => match (is_digits(x), is_digits(y)) {
+                (true, true) => {
+                    let (x, y) = (x.trim_start_matches('0'), y.trim_start_matches('0'));
+                    x.len().cmp(&y.len()).then_with(|| x.cmp(y))
+                }
+                _ => x
+                    .chars()
+                    .flat_map(char::to_lowercase)
+                    .cmp(y.chars().flat_map(char::to_lowercase)),
+            },
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+/// `s` split into runs of ASCII digits and runs of everything else.
+fn chunks(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let end = rest
+            .find(|ch: char| ch.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, tail) = rest.split_at(end);
+        rest = tail;
+        Some(chunk)
+    })
+}
+
+fn is_digits(s: &str) -> bool {
+    s.bytes().all(|b| b.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(cells: &[&str]) -> ColumnType {
+        ColumnType::infer(cells.iter().copied()).kind
+    }
+
+    #[test]
+    fn infers_the_most_specific_type() {
+        assert_eq!(kind(&["1", "-2", " 30 "]), ColumnType::Integer);
+        assert_eq!(kind(&["1", "2.5", "1e-3"]), ColumnType::Float);
+        assert_eq!(kind(&["yes", "No", "TRUE"]), ColumnType::Boolean);
+        assert_eq!(kind(&["2024-03-09", "2024/3/9"]), ColumnType::Date);
+        assert_eq!(
+            kind(&["2024-03-09T14:30", "2024-03-09 14:30:05Z"]),
+            ColumnType::DateTime
+        );
+        assert_eq!(kind(&["1", "a"]), ColumnType::Text);
+        assert_eq!(kind(&["", "  "]), ColumnType::Text);
+        assert_eq!(kind(&[]), ColumnType::Text);
+    }
+
+    #[test]
+    fn infers_a_wider_type_that_fits_more_cells() {
+        let cells = ["1", "2", "3", "4", "5", "6", "7", "8", "9", "1.5"];
+        let guess = ColumnType::infer(cells);
+        assert_eq!(guess.kind, ColumnType::Float);
+        assert_eq!(guess.confidence, 1.0);
+        let mut cells = vec!["2024-03-09"; 9];
+        cells.push("2024-03-09 14:30");
+        assert_eq!(kind(&cells), ColumnType::DateTime);
+    }
+
+    #[test]
+    fn infers_despite_a_few_stray_cells() {
+        let mut cells = vec!["7"; 9];
+        cells.push("n/a");
+        cells.push("");
+        let guess = ColumnType::infer(cells.iter().copied());
+        assert_eq!(guess.kind, ColumnType::Integer);
+        assert_eq!(guess.confidence, 0.9);
+        cells.push("n/a");
+        assert_eq!(kind(&cells), ColumnType::Text);
+    }
+
+    #[test]
+    fn checks_cells_fit() {
+        assert!(ColumnType::Integer.fits(" 42 "));
+        assert!(!ColumnType::Integer.fits("4.2"));
+        assert!(ColumnType::Float.fits("4.2"));
+        assert!(!ColumnType::Float.fits("inf"));
+        assert!(!ColumnType::Float.fits("NaN"));
+        assert!(ColumnType::Boolean.fits("Yes"));
+        assert!(!ColumnType::Boolean.fits("1"));
+        assert!(ColumnType::Date.fits("2024-12-31"));
+        assert!(!ColumnType::Date.fits("2024-12-31 10:00"));
+        assert!(ColumnType::DateTime.fits("2024-12-31 10:00"));
+        assert!(ColumnType::Text.fits("anything"));
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(parse_date("2024-03-09"), Some((2024, 3, 9)));
+        assert_eq!(parse_date("2024/3/9"), Some((2024, 3, 9)));
+        assert_eq!(parse_date("2024-13-01"), None);
+        assert_eq!(parse_date("2024-00-01"), None);
+        assert_eq!(parse_date("2024-01-32"), None);
+        assert_eq!(parse_date("24-01-01"), None);
+        assert_eq!(parse_date("2024-01-01-01"), None);
+        assert_eq!(parse_date("09.03.2024"), None);
+    }
+
+    #[test]
+    fn parses_datetimes() {
+        let date = (2024, 3, 9);
+        assert_eq!(parse_datetime("2024-03-09"), Some((date, 0.0)));
+        assert_eq!(parse_datetime("2024-03-09T14:30"), Some((date, 52200.0)));
+        assert_eq!(parse_datetime("2024-03-09 14:30:05"), Some((date, 52205.0)));
+        assert_eq!(parse_datetime("2024-03-09T00:00:01.5Z"), Some((date, 1.5)));
+        assert_eq!(parse_datetime("2024-03-09 24:00"), None);
+        assert_eq!(parse_datetime("2024-03-09 12:60"), None);
+        assert_eq!(parse_datetime("2024-03-09 1:00"), None);
+        assert_eq!(parse_datetime("2024-03-09 12:00:00:00"), None);
+        assert_eq!(parse_datetime("2024-03-09 noon"), None);
+    }
+
+    #[test]
+    fn orders_text_naturally() {
+        let mut words = ["item10", "Item2", "item2", "item1", "a", "item02", "B"];
+        words.sort_by(|a, b| natural(a, b));
+        assert_eq!(
+            words,
+            ["a", "B", "item1", "Item2", "item02", "item2", "item10"]
+        );
+    }
+
+    #[test]
+    fn compares_by_type_with_misfits_last() {
+        assert_eq!(ColumnType::Integer.compare("9", "10"), Ordering::Less);
+        assert_eq!(ColumnType::Float.compare("x", "10"), Ordering::Greater);
+        assert_eq!(ColumnType::Boolean.compare("no", "yes"), Ordering::Less);
+        assert_eq!(
+            ColumnType::Date.compare("2024-1-2", "2023-12-31"),
+            Ordering::Greater
+        );
+    }
+}
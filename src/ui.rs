@@ -1,17 +1,28 @@
-use std::{num::ParseFloatError, vec};
+use std::{ops::Range, vec};
+
+use unicode_segmentation::UnicodeSegmentation;
 
 use ratatui::{
-    layout::{Alignment, Constraint, Layout},
+    layout::{Alignment, Constraint, Layout, Margin, Rect},
     style::{Style, Stylize},
     symbols::Marker,
     text::{Line, Span},
     widgets::{
-        self, Axis, Block, Dataset, LegendPosition, Paragraph, Row, StatefulWidget, TableState,
+        self, Axis, Block, Clear, Dataset, LegendPosition, Paragraph, Row, StatefulWidget,
+        TableState,
     },
     Frame,
 };
 
-use crate::{app::App, theme};
+use crate::{
+    app::{App, Status, TableLayout},
+    editor::LineEditor,
+    goto,
+    prompt::PromptKind,
+    search::Scope,
+    theme,
+    types::{self, TypeGuess},
+};
 
 /// Renders the user interface widgets.
 pub fn render(app: &mut App, frame: &mut Frame, theme: theme::Theme) {
@@ -37,111 +48,82 @@ pub fn render(app: &mut App, frame: &mut Frame, theme: theme::Theme) {
             true => Span::styled("TRUE", styles[2]),
             false => Span::styled("FALSE", styles[0]),
         },
+        match app.read_only {
+            true => Span::styled(" READ-ONLY", styles[1]).bold(),
+            false => Span::raw(""),
+        },
+        match app.col_guess(app.current_location.0) {
+            Some(guess) if !app.has_header_row && !app.is_graph => Span::raw(format!(
+                " {}: {}",
+                goto::col_letters(app.current_location.0),
+                type_tag(guess)
+            )),
+            _ => Span::raw(""),
+        },
+        match app.filtered_rows() {
+            Some((shown, total)) => Span::styled(
+                format!(" {shown} of {total} rows"),
+                // Themes may define fewer extra colours than the defaults.
+                styles
+                    .get(3)
+                    .copied()
+                    .unwrap_or(Style::new().fg(theme.text)),
+            ),
+            None => Span::raw(""),
+        },
     ]);
 
     if app.is_graph {
-        let mut encountered_err: Option<ParseFloatError> = None;
-        let rows = app.value_matrix.clone().len();
-        let cols = app
-            .value_matrix
-            .clone()
-            .iter()
-            .map(|v| v.len())
-            .max()
-            .unwrap_or(0);
-
-        let mut transpose = vec![vec![String::new(); rows]; cols];
-
-        // Transpose the data
-        for (i, col) in app.value_matrix.clone().iter().enumerate() {
-            for (j, val) in col.clone().into_iter().enumerate() {
-                transpose[j][i] = val.clone();
-            }
-        }
-
-        // Create a new vector with the correct size
-        let mut result = vec![
-            vec![String::new(); if app.has_header_row { rows - 1 } else { rows }];
-            if app.has_label_col { cols - 1 } else { cols }
-        ];
-
-        // Transpose the data
-        for (i, col) in app
-            .value_matrix
-            .clone()
-            .iter()
-            .skip(if app.has_header_row { 1 } else { 0 })
-            .enumerate()
-        {
-            for (j, val) in col
-                .clone()
-                .into_iter()
-                .skip(if app.has_label_col { 1 } else { 0 })
-                .enumerate()
-            {
-                result[j][i] = val.clone();
-            }
-        }
-        let mut upper_y = 0.0;
-        let mut lower_y = f64::INFINITY;
-        let datas: Vec<Vec<(f64, f64)>> = result
-            .clone()
-            .into_iter()
-            .map(|x| {
-                let y = x.into_iter().map(|x| match x.parse::<f64>() {
-                    Ok(j) => {
-                        if j > upper_y {
-                            upper_y = j;
-                        };
-                        if j < lower_y {
-                            lower_y = j;
-                        };
-                        j
-                    }
-                    Err(j) => {
-                        encountered_err = Some(j);
-                        0.0
-                    }
-                });
-                return y.collect::<Vec<f64>>();
-            })
-            .collect::<Vec<Vec<f64>>>()
-            .into_iter()
-            .map(|x| {
-                x.into_iter()
+        app.table_layout = TableLayout::default();
+        let area = if app.controls { content } else { frame.area() };
+        let body = &app.value_matrix[(app.has_header_row as usize).min(app.value_matrix.len())..];
+        // Only the columns holding numbers are plotted, leaving out any cell that isn't one.
+        let datas: Vec<(usize, Vec<(f64, f64)>)> = (app.has_label_col as usize..app.col_count())
+            .filter(|col| app.col_type(*col).is_numeric())
+            .map(|col| {
+                let points = body
+                    .iter()
                     .enumerate()
-                    .map(|(i, x)| (i as f64, x))
-                    .collect::<Vec<(f64, f64)>>()
+                    .filter_map(|(i, row)| Some((i as f64, types::parse_number(row[col].trim())?)))
+                    .collect();
+                (col, points)
             })
             .collect();
+        let (lower_y, upper_y) = datas
+            .iter()
+            .flat_map(|(_, points)| points)
+            .fold((f64::INFINITY, 0.0_f64), |(lower, upper), (_, y)| {
+                (lower.min(*y), upper.max(*y))
+            });
 
-        if let Some(j) = encountered_err {
+        if datas.is_empty() {
             frame.render_widget(
                 Block::bordered()
-                    .title(format!("Encountered Error: {}", j))
+                    .title("Nothing to plot: no column holds numbers")
                     .title_alignment(Alignment::Center)
                     .title_bottom(bottom_title)
                     .bg(theme.background)
                     .border_style(Style::new().fg(theme.border)),
-                if app.controls { content } else { frame.area() },
+                area,
             );
         } else {
-            let mut datasets: Vec<Dataset> = Vec::new();
-            for (i, _) in datas.clone().into_iter().enumerate() {
-                datasets.push(
+            let datasets: Vec<Dataset> = datas
+                .iter()
+                .enumerate()
+                .map(|(i, (col, points))| {
                     Dataset::default()
-                        .data(&datas[i])
+                        .data(points)
                         .graph_type(widgets::GraphType::Line)
                         .name(if app.has_header_row {
-                            app.value_matrix[0][i + if app.has_label_col { 1 } else { 0 }].clone()
+                            app.value_matrix[0][*col].clone()
                         } else {
                             " ".to_string()
                         })
                         .marker(Marker::Braille)
-                        .style(styles[i % styles.len()]),
-                );
-            }
-            let upper_x = (datas[0].len() - 1) as f64;
+                        .style(styles[i % styles.len()])
+                })
+                .collect();
+            let upper_x = body.len().saturating_sub(1) as f64;
             let lower_x = 0.0;
             let chart = widgets::Chart::new(datasets)
                 .legend_position(if app.has_header_row {
@@ -153,11 +135,8 @@ pub fn render(app: &mut App, frame: &mut Frame, theme: theme::Theme) {
                     Axis::default()
                         .bounds([lower_x, upper_x])
                         .labels(if app.has_label_col {
-                            transpose.clone()[0]
-                                .clone()
-                                .into_iter()
-                                .skip(if app.has_header_row { 1 } else { 0 })
-                                .map(|x| x.fg(theme.text))
+                            body.iter()
+                                .map(|row| row[0].clone().fg(theme.text))
                                 .collect::<Vec<Span>>()
                         } else {
                             Vec::new()
@@ -187,101 +166,195 @@ pub fn render(app: &mut App, frame: &mut Frame, theme: theme::Theme) {
                 )
                 .bg(theme.background);
 
-            frame.render_widget(chart, if app.controls { content } else { frame.area() });
+            frame.render_widget(chart, area);
         }
     } else {
-        if app.value_matrix.len() == 0 {
-        } else {
-            let constraints: Vec<Constraint> = app.value_matrix.clone()[0]
-                .clone()
-                .into_iter()
-                .map(|_| Constraint::Fill(1))
-                .collect();
+        let area = if app.controls { content } else { frame.area() };
+        let header_rows = if app.has_header_row { 1 } else { 0 };
+        // Only the rows that fit between the header and the bottom border are read.
+        let visible = (area.height.saturating_sub(2 + header_rows as u16) as usize).max(1);
+        app.page_rows = visible;
+        let cursor = app.view_index(app.current_location.1);
+        let first = scroll_rows(&mut app.row_offset, header_rows, cursor, visible);
+        let shown: Vec<usize> = (first..(first + visible).min(app.shown_rows()))
+            .map(|i| app.view_row(i))
+            .collect();
+        let mut rows: Vec<Vec<String>> = shown.iter().map(|row| app.row(*row)).collect();
+        if app.has_header_row {
+            rows.insert(0, app.row(0));
+        }
+        let mut widths = column_widths(&rows, app.col_count());
+        if app.has_header_row {
+            for (col, width) in widths.iter_mut().enumerate() {
+                let header = Span::raw(one_line(&rows[0][col])).width();
+                let tag = app
+                    .col_guess(col)
+                    .map_or(0, |guess| 1 + type_tag(guess).len());
+                let tagged = header + tag;
+                *width = (*width).max(tagged.min(MAX_COL_WIDTH as usize) as u16);
+            }
+        }
+        for (col, width) in &app.col_widths {
+            if let Some(fitted) = widths.get_mut(*col) {
+                *fitted = *width;
+            }
+        }
+        let frozen = app.frozen_cols();
+        let scrolled = scroll_columns(
+            &widths,
+            frozen,
+            &mut app.col_offset,
+            app.current_location.0,
+            area.width.saturating_sub(2),
+        );
+        let hidden_left = scrolled.start - frozen;
+        let hidden_right = widths.len() - scrolled.end;
+        let columns: Vec<usize> = (0..frozen).chain(scrolled).collect();
+        app.table_layout = table_layout(
+            area.inner(Margin::new(1, 1)),
+            app.has_header_row,
+            shown.clone(),
+            &columns,
+            &widths,
+            frozen,
+        );
 
-            let rows = app
-                .value_matrix
-                .clone()
-                .into_iter()
-                .enumerate()
-                .map(|(i, x)| {
-                    let row = Row::new(x.into_iter().enumerate().map(|(j, x)| {
-                        if (j, i) == app.current_location {
-                            match app.editing {
-                                true => app
-                                    .current_value
-                                    .clone()
-                                    .bold()
-                                    .bg(theme.header_background)
-                                    .fg(theme.header_text),
-                                false => {
-                                    app.current_value.clone().bold().underlined().fg(theme.text)
-                                }
-                            }
-                        } else if j == 0 && app.has_label_col {
-                            x.bold().fg(theme.text)
-                        } else {
-                            x.fg(theme.text)
-                        }
-                    }));
-                    if i % 2 == 0 {
-                        row.bg(theme.highlight_background).fg(theme.highlight_text)
-                    } else {
-                        row
-                    }
-                });
+        let mut body: Vec<Row> = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| {
+                let i = if app.has_header_row && i == 0 {
+                    0
+                } else {
+                    shown[i - header_rows]
+                };
+                table_row(app, &theme, i, x, &columns, &widths, frozen)
+            })
+            .collect();
+        let header = if app.has_header_row {
+            body.remove(0)
+                .fg(theme.header_text)
+                .bg(theme.header_background)
+                .italic()
+        } else {
+            Row::default()
+        };
+        let mut constraints: Vec<Constraint> = columns
+            .iter()
+            .map(|col| Constraint::Length(widths[*col]))
+            .collect();
+        if frozen > 0 && frozen < columns.len() {
+            constraints.insert(frozen, Constraint::Length(1));
+        }
 
-            let mut boxes_state = TableState::default();
-            boxes_state.scroll_down_by(app.current_location.1 as u16);
-            let _ = widgets::Table::new(
-                rows.clone()
-                    .skip(if app.has_header_row { 1 } else { 0 })
-                    .collect::<Vec<Row>>(),
-                constraints,
-            )
+        widgets::Table::new(body, constraints)
             .block(
                 Block::bordered()
-                .title_alignment(Alignment::Center)
+                    .title_alignment(Alignment::Center)
+                    .title(match hidden_left {
+                        0 => Line::default(),
+                        n => Line::from(format!("◀ {n} more")).left_aligned(),
+                    })
+                    .title(match hidden_right {
+                        0 => Line::default(),
+                        n => Line::from(format!("{n} more ▶")).right_aligned(),
+                    })
                     .title_bottom(bottom_title)
                     .bg(theme.background)
                     .border_style(Style::new().fg(theme.border)),
             )
-            .header(if app.has_header_row {
-                rows.collect::<Vec<Row>>()[0]
-                    .clone()
-                    .fg(theme.header_text)
-                    .bg(theme.header_background)
-                    .italic()
-            } else {
-                Row::default()
-            })
+            .header(header)
             .bg(theme.background)
-            .render(
-                if app.controls { content } else { frame.area() },
-                frame.buffer_mut(),
-                &mut boxes_state,
-            );
-        }
+            .render(area, frame.buffer_mut(), &mut TableState::default());
     }
     if app.controls {
-        let _ = widgets::Table::new(
+        widgets::Table::new(
             match app.editing {
+                _ if app.reordering.is_some() => vec![
+                    Row::new(vec!["Up/Down", "Move Cursor"]).fg(theme.text),
+                    Row::new(vec!["Space", "Grab/Drop Col"]).fg(theme.text),
+                    Row::new(vec!["ALT+Up/Down", "Move Col"]).fg(theme.text),
+                    Row::new(vec!["Drag", "Move Col"]).fg(theme.text),
+                    Row::new(vec!["Enter", "Apply"]).fg(theme.text),
+                    Row::new(vec!["Esc", "Cancel"]).fg(theme.text),
+                ],
+                _ if app.replacing.is_some() => vec![
+                    Row::new(vec!["y", "Replace"]).fg(theme.text),
+                    Row::new(vec!["n", "Skip"]).fg(theme.text),
+                    Row::new(vec!["a", "Replace All Left"]).fg(theme.text),
+                    Row::new(vec!["q/Esc", "Stop"]).fg(theme.text),
+                ],
+                _ if app.prompt.as_ref().is_some_and(|p| !p.kind.searches()) => vec![
+                    Row::new(vec!["Enter", "Go"]).fg(theme.text),
+                    Row::new(vec!["Esc", "Cancel"]).fg(theme.text),
+                ],
+                _ if app.prompt.is_some() => vec![
+                    Row::new(vec!["Enter", "Done"]).fg(theme.text),
+                    Row::new(vec!["Esc", "Cancel"]).fg(theme.text),
+                    Row::new(vec!["ALT+C", "Match Case"]).fg(theme.text),
+                    Row::new(vec!["ALT+R", "Regex"]).fg(theme.text),
+                    Row::new(vec!["ALT+K", "Sheet/Col/Selection"]).fg(theme.text),
+                    Row::new(vec!["ALT+A", "Replace All"]).fg(theme.text),
+                ],
                 false => vec![
                     Row::new(vec!["Enter", "Enter Editing"]).fg(theme.text),
+                    Row::new(vec!["e", "Edit in Popup"]).fg(theme.text),
                     Row::new(vec!["Arrows", "Move Selection"]).fg(theme.text),
+                    Row::new(vec!["PgUp/PgDn", "Move a Page"]).fg(theme.text),
+                    Row::new(vec!["Home/End", "First/Last Col"]).fg(theme.text),
+                    Row::new(vec!["CTR+Home/End", "First/Last Cell"]).fg(theme.text),
+                    Row::new(vec!["Click", "Select/Sort by Header"]).fg(theme.text),
+                    Row::new(vec!["Double Click", "Enter Editing"]).fg(theme.text),
+                    Row::new(vec!["Wheel", "Scroll (+Shift Cols)"]).fg(theme.text),
+                    Row::new(vec!["Drag Border", "Resize Col"]).fg(theme.text),
                     Row::new(vec!["q/CTR+C", "Exit"]).fg(theme.text),
+                    Row::new(vec!["CTR+S", "Save"]).fg(theme.text),
                     Row::new(vec!["CTR+Z", "Undo"]).fg(theme.text),
+                    Row::new(vec!["CTR+Y", "Redo"]).fg(theme.text),
                     Row::new(vec!["h", "Toggle Header Row"]).fg(theme.text),
                     Row::new(vec!["j", "Toggle Label Col"]).fg(theme.text),
-                    Row::new(vec!["y", "Add Row"]).fg(theme.text),
-                    Row::new(vec!["n", "Remove Row"]).fg(theme.text),
-                    Row::new(vec!["u", "Add Col"]).fg(theme.text),
-                    Row::new(vec!["m", "Remove Col"]).fg(theme.text),
+                    Row::new(vec!["[ / ]", "Freeze Fewer/More Cols"]).fg(theme.text),
+                    Row::new(vec!["y/Y", "Insert Row Below/Above"]).fg(theme.text),
+                    Row::new(vec!["b", "Delete Row"]).fg(theme.text),
+                    Row::new(vec!["u/U", "Insert Col Right/Left"]).fg(theme.text),
+                    Row::new(vec!["m", "Delete Col"]).fg(theme.text),
+                    Row::new(vec!["ALT+Arrows", "Move Row/Col"]).fg(theme.text),
+                    Row::new(vec!["o", "Reorder Cols"]).fg(theme.text),
+                    Row::new(vec!["s/S", "Sort Asc/Desc"]).fg(theme.text),
+                    Row::new(vec!["ALT+S", "Sort by Columns"]).fg(theme.text),
+                    Row::new(vec!["f", "Filter Rows"]).fg(theme.text),
+                    Row::new(vec!["F", "Show All Rows"]).fg(theme.text),
+                    Row::new(vec!["v/SHIFT+Arrows", "Select"]).fg(theme.text),
+                    Row::new(vec!["y/x/d", "Copy/Cut/Clear Sel"]).fg(theme.text),
+                    Row::new(vec!["p", "Paste"]).fg(theme.text),
+                    Row::new(vec!["/", "Search"]).fg(theme.text),
+                    Row::new(vec!["n/N", "Next/Prev Match"]).fg(theme.text),
+                    Row::new(vec!["r", "Find and Replace"]).fg(theme.text),
+                    Row::new(vec![":", "Go to Cell"]).fg(theme.text),
                     Row::new(vec!["k", "Toggle Graph"]).fg(theme.text),
                     Row::new(vec!["c", "Toggle Control Panel"]).fg(theme.text),
                 ],
+                true if app.multiline => vec![
+                    Row::new(vec!["CTR+D", "Exit Editing"]).fg(theme.text),
+                    Row::new(vec!["Enter", "New Line"]).fg(theme.text),
+                    Row::new(vec!["Esc", "Cancel Edit"]).fg(theme.text),
+                    Row::new(vec!["Arrows", "Move Cursor"]).fg(theme.text),
+                    Row::new(vec!["CTR+Left/Right", "Move a Word"]).fg(theme.text),
+                    Row::new(vec!["Home/End", "Start/End"]).fg(theme.text),
+                    Row::new(vec!["Del", "Delete Forward"]).fg(theme.text),
+                    Row::new(vec!["CTR+W", "Delete Word"]).fg(theme.text),
+                    Row::new(vec!["CTR+U", "Delete to Start"]).fg(theme.text),
+                    Row::new(vec!["CTR+C", "Exit"]).fg(theme.text),
+                ],
                 true => vec![
                     Row::new(vec!["Enter", "Exit Editing"]).fg(theme.text),
-                    Row::new(vec!["Arrows", "Move Cursor"]).fg(theme.text),
+                    Row::new(vec!["Esc", "Cancel Edit"]).fg(theme.text),
+                    Row::new(vec!["Left/Right", "Move Cursor"]).fg(theme.text),
+                    Row::new(vec!["CTR+Left/Right", "Move a Word"]).fg(theme.text),
+                    Row::new(vec!["Home/End", "Start/End"]).fg(theme.text),
+                    Row::new(vec!["Del", "Delete Forward"]).fg(theme.text),
+                    Row::new(vec!["CTR+W", "Delete Word"]).fg(theme.text),
+                    Row::new(vec!["CTR+U", "Delete to Start"]).fg(theme.text),
                     Row::new(vec!["CTR+C", "Exit"]).fg(theme.text),
                 ],
             },
@@ -291,15 +364,469 @@ pub fn render(app: &mut App, frame: &mut Frame, theme: theme::Theme) {
             Block::bordered()
                 .title("Controls Panel")
                 .title_alignment(Alignment::Center)
-                .title_bottom(format!("Edits: {}", app.previous_matrices.len()))
+                .title_bottom(format!(
+                    "Edits: {}/{}",
+                    app.history.position(),
+                    app.history.total()
+                ))
                 .bg(theme.background)
                 .border_style(Style::new().fg(theme.border)),
         )
         .bg(theme.background)
         .render(controls, frame.buffer_mut(), &mut TableState::default());
     }
-    let path = Paragraph::new(app.path.clone())
-        .fg(theme.path_text)
-        .bg(theme.path_background);
-    frame.render_widget(path, path_bar);
+    let path = Paragraph::new(Line::from(vec![
+        Span::raw(app.path.clone()),
+        match app.stream.as_ref().and_then(|stream| stream.progress()) {
+            Some((scanned, len)) => Span::styled(
+                format!(
+                    " [indexing {}% {} rows]",
+                    scanned * 100 / len.max(1),
+                    app.row_count()
+                ),
+                styles[3 % styles.len()],
+            ),
+            None => Span::raw(""),
+        },
+        match app.selection() {
+            Some((rows, cols)) => Span::styled(
+                format!(" [{} by {} selected]", rows.len(), cols.len()),
+                styles[4 % styles.len()],
+            ),
+            None => Span::raw(""),
+        },
+        if app.dirty {
+            Span::styled(" [modified]", styles[1])
+        } else {
+            Span::raw("")
+        },
+        match &app.status {
+            Some(Status::Info(message)) => Span::styled(format!("  {message}"), styles[2]),
+            Some(Status::Error(message)) => Span::styled(format!("  {message}"), styles[0]),
+            None => Span::raw(""),
+        },
+    ]))
+    .fg(theme.path_text)
+    .bg(theme.path_background);
+    match &app.prompt {
+        Some(prompt) => {
+            let mut line = editing_line(&prompt.editor, path_bar.width / 2);
+            line.spans.insert(0, Span::raw(prompt.kind.label()));
+            match prompt.kind {
+                PromptKind::Search | PromptKind::Find | PromptKind::Replace => line.spans.extend([
+                    flag("Aa", app.search.case_sensitive, styles[2]),
+                    flag(".*", app.search.regex, styles[2]),
+                    match app.search.scope {
+                        Scope::Sheet => flag("col", false, styles[2]),
+                        Scope::Column(_) => flag("col", true, styles[2]),
+                        Scope::Selection { .. } => flag("sel", true, styles[2]),
+                    },
+                    match (app.search.invalid, app.search.matches.len()) {
+                        (true, _) => Span::styled("  invalid pattern", styles[0]),
+                        (false, n) if prompt.kind == PromptKind::Replace => {
+                            Span::raw(format!("  {n} cells to change"))
+                        }
+                        (false, 1) => Span::raw("  1 match"),
+                        (false, n) => Span::raw(format!("  {n} matches")),
+                    },
+                ]),
+                PromptKind::Goto => line
+                    .spans
+                    .push(Span::raw("  e.g. C12, row 12, col \"Total\"").dim()),
+                PromptKind::Filter => line
+                    .spans
+                    .push(Span::raw("  = != ~ =~ < > in 1..9 empty, joined by and").dim()),
+                PromptKind::Sort => line
+                    .spans
+                    .push(Span::raw("  e.g. C desc, A, \"Total\" desc").dim()),
+            }
+            frame.render_widget(
+                Paragraph::new(line)
+                    .fg(theme.path_text)
+                    .bg(theme.path_background),
+                path_bar,
+            );
+        }
+        None => match &app.replacing {
+            Some(replace) => frame.render_widget(
+                Paragraph::new(format!(
+                    "Replace this cell? (y) yes  (n) skip  (a) all  (q) stop  {} left",
+                    replace.pending.len()
+                ))
+                .fg(theme.path_text)
+                .bg(theme.path_background),
+                path_bar,
+            ),
+            None => frame.render_widget(path, path_bar),
+        },
+    }
+
+    if app.multiline {
+        let area = popup_area(
+            frame.area(),
+            frame.area().width * 4 / 5,
+            frame.area().height * 4 / 5,
+        );
+        let inner = area.inner(Margin::new(1, 1));
+        let (lines, cursor_row) = wrapped_lines(&app.editor, inner.width.max(1) as usize);
+        let scroll = cursor_row.saturating_sub(inner.height.saturating_sub(1) as usize);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .scroll((scroll as u16, 0))
+                .fg(theme.text)
+                .block(
+                    Block::bordered()
+                        .title(format!(
+                            "Cell {}:{}",
+                            app.current_location.1 + 1,
+                            app.current_location.0 + 1
+                        ))
+                        .title_alignment(Alignment::Center)
+                        .title_bottom("CTR+D done  Esc cancel")
+                        .bg(theme.background)
+                        .border_style(Style::new().fg(theme.border)),
+                ),
+            area,
+        );
+    }
+
+    if let Some(reorder) = &mut app.reordering {
+        let area = popup_area(
+            frame.area(),
+            50,
+            (reorder.order.len() as u16 + 2).min(frame.area().height * 4 / 5),
+        );
+        let inner = area.inner(Margin::new(1, 1));
+        let shown = (inner.height as usize).max(1);
+        reorder.top = reorder
+            .top
+            .min(reorder.selected)
+            .max((reorder.selected + 1).saturating_sub(shown));
+        reorder.area = inner;
+        let lines: Vec<Line> = reorder
+            .order
+            .iter()
+            .enumerate()
+            .skip(reorder.top)
+            .take(shown)
+            .map(|(place, col)| {
+                let line = Line::from(format!(
+                    "{:>3}. {:<3} {}",
+                    place + 1,
+                    goto::col_letters(*col),
+                    one_line(app.value_matrix[0][*col].trim())
+                ));
+                match (place == reorder.selected, reorder.grabbed) {
+                    (true, true) => line.fg(theme.header_text).bg(theme.header_background),
+                    (true, false) => line.reversed(),
+                    (false, _) => line,
+                }
+            })
+            .collect();
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(lines).fg(theme.text).block(
+                Block::bordered()
+                    .title("Reorder Columns")
+                    .title_alignment(Alignment::Center)
+                    .title_bottom("Space/Drag move  Enter apply  Esc cancel")
+                    .bg(theme.background)
+                    .border_style(Style::new().fg(theme.border)),
+            ),
+            area,
+        );
+    }
+
+    if app.confirming_quit {
+        let area = popup_area(frame.area(), 46, 4);
+        frame.render_widget(Clear, area);
+        frame.render_widget(
+            Paragraph::new(vec![
+                Line::from("You have unsaved changes."),
+                Line::from("(y) save and quit  (n) discard  (c) cancel"),
+            ])
+            .alignment(Alignment::Center)
+            .fg(theme.text)
+            .block(
+                Block::bordered()
+                    .title("Quit")
+                    .title_alignment(Alignment::Center)
+                    .bg(theme.background)
+                    .border_style(Style::new().fg(theme.border)),
+            ),
+            area,
+        );
+    }
+}
+
+/// Narrowest a column is drawn.
+const MIN_COL_WIDTH: u16 = 3;
+
+/// Widest a column is drawn before its content is cut off.
+const MAX_COL_WIDTH: u16 = 30;
+
+/// Width of each of `cols` columns, fitting every cell in `rows` as drawn on one line.
+fn column_widths(rows: &[Vec<String>], cols: usize) -> Vec<u16> {
+    let mut widths = vec![MIN_COL_WIDTH; cols];
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            let cell = Span::raw(one_line(cell)).width();
+            *width = (*width).max(cell.min(MAX_COL_WIDTH as usize) as u16);
+        }
+    }
+    widths
+}
+
+/// Scrolls `offset` just enough to keep the `cursor` row among the
+/// `visible` rows shown below the `header_rows`, and returns it. A cursor
+/// on the header scrolls back to the top.
+fn scroll_rows(offset: &mut usize, header_rows: usize, cursor: usize, visible: usize) -> usize {
+    *offset = (*offset).max(header_rows);
+    if cursor < *offset {
+        *offset = cursor.max(header_rows);
+    } else if cursor >= *offset + visible {
+        *offset = cursor + 1 - visible;
+    }
+    *offset
+}
+
+/// Scrolls `offset` so the `cursor` column is on screen and returns the
+/// columns after the `frozen` ones that fit in `available` cells.
+///
+/// Columns are one cell apart, and frozen columns are followed by a
+/// one-cell separator.
+fn scroll_columns(
+    widths: &[u16],
+    frozen: usize,
+    offset: &mut usize,
+    cursor: usize,
+    available: u16,
+) -> Range<usize> {
+    let span = |columns: &[u16]| {
+        columns
+            .iter()
+            .map(|width| *width as usize + 1)
+            .sum::<usize>()
+    };
+    let available = (available as usize + 1).saturating_sub(match frozen {
+        0 => 0,
+        _ => span(&widths[..frozen]) + 2,
+    });
+    if cursor >= frozen {
+        *offset = (*offset).clamp(frozen, cursor);
+        while *offset < cursor && span(&widths[*offset..=cursor]) > available {
+            *offset += 1;
+        }
+    } else {
+        *offset = (*offset).max(frozen);
+    }
+    let mut end = *offset;
+    while end < widths.len() && (end == *offset || span(&widths[*offset..=end]) <= available) {
+        end += 1;
+    }
+    *offset..end
+}
+
+/// Records where `columns` of the given `widths` land inside `area`, for
+/// [`TableLayout::hit`]. The separator after the `frozen` columns takes up
+/// a cell and its spacing.
+fn table_layout(
+    area: Rect,
+    header: bool,
+    rows: Vec<usize>,
+    columns: &[usize],
+    widths: &[u16],
+    frozen: usize,
+) -> TableLayout {
+    let mut x = area.x;
+    let mut spans = Vec::new();
+    for (k, col) in columns.iter().enumerate() {
+        if k == frozen && frozen > 0 {
+            x += 2;
+        }
+        spans.push((*col, x, widths[*col]));
+        x += widths[*col] + 1;
+    }
+    TableLayout {
+        area,
+        header,
+        rows,
+        columns: spans,
+    }
+}
+
+/// Styles the `columns` of row `i` of the table, with a separator after the `frozen` ones.
+fn table_row<'a>(
+    app: &App,
+    theme: &theme::Theme,
+    i: usize,
+    mut cells: Vec<String>,
+    columns: &[usize],
+    widths: &[u16],
+    frozen: usize,
+) -> Row<'a> {
+    let selection = app.selection();
+    let header = app.has_header_row && i == 0;
+    let mut row: Vec<Line> = columns
+        .iter()
+        .map(|j| {
+            let x = std::mem::take(&mut cells[*j]);
+            let kind = app.col_type(*j);
+            let line = cell_line(app, theme, (i, *j), x, widths[*j], selection.as_ref());
+            if header {
+                let mut line = line;
+                if let Some(guess) = app.col_guess(*j) {
+                    line.push_span(Span::raw(format!(" {}", type_tag(guess))).dim());
+                }
+                line
+            } else if kind.is_numeric() && !(app.editing && (*j, i) == app.current_location) {
+                line.right_aligned()
+            } else {
+                line
+            }
+        })
+        .collect();
+    if frozen > 0 && frozen < columns.len() {
+        row.insert(frozen, Line::from("│").fg(theme.border));
+    }
+    let row = Row::new(row);
+    if i.is_multiple_of(2) {
+        row.bg(theme.highlight_background).fg(theme.highlight_text)
+    } else {
+        row
+    }
+}
+
+/// Styles the cell at row `i`, column `j` holding `x`, `width` cells
+/// wide. Cells that don't fit the type of their column are drawn in the
+/// first extra colour.
+fn cell_line<'a>(
+    app: &App,
+    theme: &theme::Theme,
+    (i, j): (usize, usize),
+    x: String,
+    width: u16,
+    selection: Option<&(Range<usize>, Range<usize>)>,
+) -> Line<'a> {
+    let text =
+        match (app.has_header_row && i == 0) || x.trim().is_empty() || app.col_type(j).fits(&x) {
+            true => theme.text,
+            false => theme.extra_colors.first().copied().unwrap_or(theme.text),
+        };
+    if (j, i) == app.current_location {
+        match app.editing {
+            true => editing_line(&app.editor, width)
+                .bold()
+                .bg(theme.header_background)
+                .fg(theme.header_text),
+            false => Line::from(one_line(&app.editor.value).bold().underlined()).fg(text),
+        }
+    } else if selection.is_some_and(|(rows, cols)| rows.contains(&i) && cols.contains(&j)) {
+        Line::from(one_line(&x))
+            .bg(theme.header_background)
+            .fg(theme.header_text)
+    } else if app.search.matches.contains(&(i, j)) {
+        Line::from(one_line(&x)).fg(text).reversed()
+    } else if j == 0 && app.has_label_col {
+        Line::from(one_line(&x).bold()).fg(text)
+    } else {
+        Line::from(one_line(&x)).fg(text)
+    }
+}
+
+/// Type of a column as shown in the header, with how sure the guess is when it isn't certain.
+fn type_tag(guess: TypeGuess) -> String {
+    match guess.confidence < 1.0 {
+        true => format!(
+            "{} {:.0}%",
+            guess.kind.name(),
+            (guess.confidence * 100.0).floor()
+        ),
+        false => guess.kind.name().to_string(),
+    }
+}
+
+/// The cell being edited with the cursor shown reversed, scrolled so the
+/// cursor stays within `width` cells.
+fn editing_line<'a>(editor: &LineEditor, width: u16) -> Line<'a> {
+    let (mut before, at, after) = editor.split();
+    let at = if at.is_empty() { " " } else { at };
+    let room = (width as usize).saturating_sub(Span::raw(at).width());
+    while Span::raw(before).width() > room {
+        let first = before.graphemes(true).next().unwrap_or_default();
+        before = &before[first.len()..];
+    }
+    Line::from(vec![
+        Span::raw(one_line(before)),
+        Span::raw(one_line(at)).reversed(),
+        Span::raw(one_line(after)),
+    ])
+}
+
+/// A search option named `name`, in `style` when it is `on` and dimmed otherwise.
+fn flag<'a>(name: &str, on: bool, style: Style) -> Span<'a> {
+    match on {
+        true => Span::styled(format!(" [{name}]"), style),
+        false => Span::raw(format!(" [{name}]")).dim(),
+    }
+}
+
+/// `text` with each line break shown as `↵`, so a cell never spills onto the next row.
+fn one_line(text: &str) -> String {
+    match text.contains(['\r', '\n']) {
+        true => text.replace("\r\n", "↵").replace(['\r', '\n'], "↵"),
+        false => text.to_string(),
+    }
+}
+
+/// The text of `editor` broken into lines of at most `width` cells, with
+/// the cursor shown reversed, and the line the cursor is on.
+fn wrapped_lines<'a>(editor: &LineEditor, width: usize) -> (Vec<Line<'a>>, usize) {
+    let mut lines = vec![Line::default()];
+    let mut used = 0;
+    let mut cursor_row = 0;
+    let mut offset = 0;
+    // The extra slot after the last grapheme is where the cursor sits at the end.
+    for grapheme in editor.value.graphemes(true).map(Some).chain([None]) {
+        let line_break = matches!(grapheme, Some("\n" | "\r\n" | "\r"));
+        let at_cursor = offset == editor.cursor;
+        let shown = match grapheme {
+            Some(grapheme) if !line_break => grapheme,
+            _ => " ",
+        };
+        let shown_width = Span::raw(shown).width();
+        if used + shown_width > width && used > 0 {
+            lines.push(Line::default());
+            used = 0;
+        }
+        let row = lines.len() - 1;
+        let line = &mut lines[row];
+        if at_cursor {
+            cursor_row = row;
+            line.push_span(Span::raw(shown.to_string()).reversed());
+            used += shown_width;
+        } else if !line_break && grapheme.is_some() {
+            line.push_span(Span::raw(shown.to_string()));
+            used += shown_width;
+        }
+        if line_break {
+            lines.push(Line::default());
+            used = 0;
+        }
+        offset += grapheme.map_or(0, str::len);
+    }
+    (lines, cursor_row)
+}
+
+/// Centers a `width` by `height` box inside `area`, shrinking it to fit.
+fn popup_area(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
 }